@@ -0,0 +1,213 @@
+use std::time::{Duration, SystemTime};
+
+use rumq_client::{Publish, QoS, Request, Subscribe};
+use slog::Logger;
+use tokio::sync::mpsc::Sender;
+
+use crate::app::publisher::{NodeProperty, PublishingInfo, Topic};
+use crate::app::quota::QuotaTracker;
+use crate::app::tasks::create_publish_request;
+
+/// Shortest fetch interval settable at runtime
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Instructions of the weather fetcher received over MQTT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FetcherCommand {
+    /// Fetches the weather right away, the regular schedule continues
+    Refresh,
+    /// Restarts the schedule with the new period
+    SetInterval(Duration),
+}
+
+/// Topics the commands are received on and answered to
+#[derive(Debug, Clone)]
+pub struct CommandTopics {
+    pub refresh: String,
+    pub interval: String,
+    pub status: String,
+}
+
+impl CommandTopics {
+    pub fn from_publishing_args(args: &dyn PublishingInfo) -> Self {
+        CommandTopics {
+            refresh: NodeProperty::weather(args, "refresh/set").get_value(),
+            interval: NodeProperty::weather(args, "interval/set").get_value(),
+            status: NodeProperty::weather(args, "status").get_value(),
+        }
+    }
+
+    pub fn subscribe_request(&self) -> Request {
+        let mut subscribe = Subscribe::new(self.refresh.as_str(), QoS::AtLeastOnce);
+        subscribe.add(self.interval.clone(), QoS::AtLeastOnce);
+
+        Request::Subscribe(subscribe)
+    }
+
+    /// Command of the message, `None` for a foreign topic
+    pub fn parse(
+        &self,
+        topic: &str,
+        payload: &str,
+        max_interval: Duration,
+    ) -> Option<Result<FetcherCommand, String>> {
+        if topic == self.refresh {
+            return Some(Ok(FetcherCommand::Refresh));
+        }
+        if topic != self.interval {
+            return None;
+        }
+
+        let interval = match payload.trim().parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => return Some(Err(format!("invalid interval \"{}\"", payload))),
+        };
+        if interval < MIN_INTERVAL || interval > max_interval {
+            return Some(Err(format!(
+                "interval must be between {} and {} s",
+                MIN_INTERVAL.as_secs(),
+                max_interval.as_secs()
+            )));
+        }
+
+        Some(Ok(FetcherCommand::SetInterval(interval)))
+    }
+}
+
+/// Passes commands from the MQTT loop to the fetcher and answers on the status topic
+pub struct CommandHandler {
+    pub topics: CommandTopics,
+    pub fetcher_tx: Sender<FetcherCommand>,
+    pub requests_tx: Sender<Request>,
+    /// The configured interval, longer ones would fail the liveness probe
+    pub max_interval: Duration,
+    /// Refreshes are refused while they would exceed the API call budget
+    pub quota: Option<QuotaTracker>,
+}
+
+impl CommandHandler {
+    /// Never waits, the MQTT loop calling it also drains the requests
+    pub fn handle(&mut self, publish: &Publish, logger: &Logger) {
+        let payload = String::from_utf8_lossy(&publish.payload);
+        let command = match self
+            .topics
+            .parse(&publish.topic_name, &payload, self.max_interval)
+        {
+            Some(command) => command,
+            None => return,
+        };
+
+        let wait = match (&command, &self.quota) {
+            (Ok(FetcherCommand::Refresh), Some(quota)) => quota.wait_before_call(SystemTime::now()),
+            _ => Duration::default(),
+        };
+
+        let response = match command {
+            Ok(_) if wait > Duration::default() => {
+                format!("over budget, try again in {} s", wait.as_secs())
+            }
+            Ok(command) => match self.fetcher_tx.try_send(command) {
+                Ok(()) => match command {
+                    FetcherCommand::Refresh => "refreshing".to_string(),
+                    FetcherCommand::SetInterval(interval) => {
+                        format!("interval set to {} s", interval.as_secs())
+                    }
+                },
+                Err(_) => "busy, try again later".to_string(),
+            },
+            Err(error) => error,
+        };
+
+        slog::slog_info!(logger, "MQTT command received";
+            "topic" => &publish.topic_name, "response" => &response);
+        let request = create_publish_request(response, &self.topics.status, false);
+        if self.requests_tx.try_send(request).is_err() {
+            slog::slog_warn!(logger, "MQTT command response dropped, the queue is full");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::status::StatusBoard;
+    use crate::domain::quota::{ApiUsage, QuotaBudget};
+
+    const MAX: Duration = Duration::from_secs(600);
+
+    fn topics() -> CommandTopics {
+        CommandTopics {
+            refresh: "node/weather/weather/-/refresh/set".to_string(),
+            interval: "node/weather/weather/-/interval/set".to_string(),
+            status: "node/weather/weather/-/status".to_string(),
+        }
+    }
+
+    #[test]
+    fn refresh_ignores_payload() {
+        assert_eq!(
+            Some(Ok(FetcherCommand::Refresh)),
+            topics().parse("node/weather/weather/-/refresh/set", "whatever", MAX)
+        );
+    }
+
+    #[test]
+    fn interval_parsed_and_bounded() {
+        let topics = topics();
+        let topic = "node/weather/weather/-/interval/set";
+
+        assert_eq!(
+            Some(Ok(FetcherCommand::SetInterval(Duration::from_secs(300)))),
+            topics.parse(topic, "300\n", MAX)
+        );
+        assert!(topics.parse(topic, "10", MAX).unwrap().is_err());
+        assert!(topics.parse(topic, "3600", MAX).unwrap().is_err());
+        assert!(topics.parse(topic, "soon", MAX).unwrap().is_err());
+    }
+
+    #[test]
+    fn foreign_topics_ignored() {
+        assert_eq!(
+            None,
+            topics().parse("node/weather/weather/-/status", "", MAX)
+        );
+    }
+
+    #[test]
+    fn refresh_refused_over_budget() {
+        let (fetcher_tx, mut fetcher_rx) = tokio::sync::mpsc::channel(1);
+        let (requests_tx, mut requests_rx) = tokio::sync::mpsc::channel(1);
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let budget = QuotaBudget {
+            daily: None,
+            monthly: Some(1),
+        };
+        let quota = QuotaTracker::new(
+            ApiUsage::default(),
+            "0123456789abcdef",
+            budget,
+            StatusBoard::new(),
+            logger.clone(),
+        );
+        quota.record_call(SystemTime::now());
+        let mut handler = CommandHandler {
+            topics: topics(),
+            fetcher_tx,
+            requests_tx,
+            max_interval: MAX,
+            quota: Some(quota),
+        };
+
+        let refresh = Publish::new("node/weather/weather/-/refresh/set", QoS::AtLeastOnce, "");
+        handler.handle(&refresh, &logger);
+
+        assert!(fetcher_rx.try_recv().is_err());
+        match requests_rx.try_recv() {
+            Ok(Request::Publish(publish)) => {
+                assert_eq!("node/weather/weather/-/status", publish.topic_name);
+                assert!(String::from_utf8_lossy(&publish.payload).starts_with("over budget"));
+            }
+            other => panic!("Expected a status message, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::str::FromStr;
+
+use uom::si::f32::{Length, MassDensity, Pressure, ThermodynamicTemperature, Velocity};
+use uom::si::{length, mass_density, pressure, velocity};
+
+use crate::arguments::Units;
+use crate::domain::pressure::PressureSettings;
+
+/// Upper wind speed limits of Beaufort forces 0 to 11 in m/s, anything faster is 12
+const BEAUFORT_LIMITS: [f32; 12] = [
+    0.5, 1.6, 3.4, 5.5, 8.0, 10.8, 13.9, 17.2, 20.8, 24.5, 28.5, 32.7,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressureUnit {
+    Pascal,
+    Hectopascal,
+    InchOfMercury,
+    MillimeterOfMercury,
+}
+
+impl PressureUnit {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["pa", "hpa", "inhg", "mmhg"]
+    }
+
+    pub fn convert(self, value: Pressure) -> f32 {
+        match self {
+            PressureUnit::Pascal => value.get::<pressure::pascal>(),
+            PressureUnit::Hectopascal => value.get::<pressure::hectopascal>(),
+            PressureUnit::InchOfMercury => value.get::<pressure::inch_of_mercury>(),
+            PressureUnit::MillimeterOfMercury => value.get::<pressure::millimeter_of_mercury>(),
+        }
+    }
+}
+
+impl FromStr for PressureUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pa" => Ok(PressureUnit::Pascal),
+            "hpa" => Ok(PressureUnit::Hectopascal),
+            "inhg" => Ok(PressureUnit::InchOfMercury),
+            "mmhg" => Ok(PressureUnit::MillimeterOfMercury),
+            _ => anyhow::bail!("Unknown pressure unit \"{}\"", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpeedUnit {
+    MeterPerSecond,
+    KilometerPerHour,
+    MilePerHour,
+    Knot,
+    Beaufort,
+}
+
+impl SpeedUnit {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["m/s", "km/h", "mph", "knots", "beaufort"]
+    }
+
+    pub fn convert(self, value: Velocity) -> f32 {
+        match self {
+            SpeedUnit::MeterPerSecond => value.get::<velocity::meter_per_second>(),
+            SpeedUnit::KilometerPerHour => value.get::<velocity::kilometer_per_hour>(),
+            SpeedUnit::MilePerHour => value.get::<velocity::mile_per_hour>(),
+            SpeedUnit::Knot => value.get::<velocity::knot>(),
+            SpeedUnit::Beaufort => beaufort(value) as f32,
+        }
+    }
+}
+
+impl FromStr for SpeedUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "m/s" => Ok(SpeedUnit::MeterPerSecond),
+            "km/h" => Ok(SpeedUnit::KilometerPerHour),
+            "mph" => Ok(SpeedUnit::MilePerHour),
+            "knots" => Ok(SpeedUnit::Knot),
+            "beaufort" => Ok(SpeedUnit::Beaufort),
+            _ => anyhow::bail!("Unknown speed unit \"{}\"", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrecipitationUnit {
+    Millimeter,
+    Inch,
+}
+
+impl PrecipitationUnit {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["mm", "in"]
+    }
+
+    pub fn convert(self, value: Length) -> f32 {
+        match self {
+            PrecipitationUnit::Millimeter => value.get::<length::millimeter>(),
+            PrecipitationUnit::Inch => value.get::<length::inch>(),
+        }
+    }
+}
+
+impl FromStr for PrecipitationUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mm" => Ok(PrecipitationUnit::Millimeter),
+            "in" => Ok(PrecipitationUnit::Inch),
+            _ => anyhow::bail!("Unknown precipitation unit \"{}\"", s),
+        }
+    }
+}
+
+/// Beaufort force of the wind speed
+pub fn beaufort(value: Velocity) -> u8 {
+    let speed = value.get::<velocity::meter_per_second>();
+
+    BEAUFORT_LIMITS
+        .iter()
+        .take_while(|limit| speed >= **limit)
+        .count() as u8
+}
+
+/// Number of decimal places of each quantity
+#[derive(Debug, Clone, Copy)]
+pub struct Precision {
+    pub temperature: usize,
+    pub pressure: usize,
+    pub humidity: usize,
+    pub wind_speed: usize,
+    pub precipitation: usize,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision {
+            temperature: 2,
+            pressure: 2,
+            humidity: 1,
+            wind_speed: 1,
+            precipitation: 1,
+        }
+    }
+}
+
+/// How quantities are turned into payloads
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadFormat {
+    pub units: Units,
+    pub pressure: PressureSettings,
+    pub pressure_unit: PressureUnit,
+    pub speed_unit: SpeedUnit,
+    pub precipitation_unit: PrecipitationUnit,
+    pub precision: Precision,
+}
+
+impl PayloadFormat {
+    pub fn temperature(&self, value: ThermodynamicTemperature) -> String {
+        format!(
+            "{0:.1$}",
+            self.units.convert_temperature(value),
+            self.precision.temperature
+        )
+    }
+
+    pub fn pressure(&self, value: Pressure) -> String {
+        format!(
+            "{0:.1$}",
+            self.pressure_unit.convert(value),
+            self.precision.pressure
+        )
+    }
+
+    pub fn humidity(&self, value: f32) -> String {
+        format!("{0:.1$}", value, self.precision.humidity)
+    }
+
+    /// Absolute humidity in g/m³
+    pub fn density(&self, value: MassDensity) -> String {
+        format!(
+            "{0:.1$}",
+            value.get::<mass_density::gram_per_cubic_meter>(),
+            self.precision.humidity
+        )
+    }
+
+    pub fn wind_speed(&self, value: Velocity) -> String {
+        match self.speed_unit {
+            SpeedUnit::Beaufort => beaufort(value).to_string(),
+            unit => format!("{0:.1$}", unit.convert(value), self.precision.wind_speed),
+        }
+    }
+
+    pub fn precipitation(&self, value: Length) -> String {
+        format!(
+            "{0:.1$}",
+            self.precipitation_unit.convert(value),
+            self.precision.precipitation
+        )
+    }
+}
+
+impl Default for PayloadFormat {
+    fn default() -> Self {
+        PayloadFormat {
+            units: Units::Celsius,
+            pressure: PressureSettings::default(),
+            pressure_unit: PressureUnit::Pascal,
+            speed_unit: SpeedUnit::MeterPerSecond,
+            precipitation_unit: PrecipitationUnit::Millimeter,
+            precision: Precision::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(value: f32) -> Velocity {
+        Velocity::new::<velocity::meter_per_second>(value)
+    }
+
+    #[test]
+    fn beaufort_scale() {
+        assert_eq!(0, beaufort(ms(0.2)));
+        assert_eq!(3, beaufort(ms(5.0)));
+        assert_eq!(6, beaufort(ms(10.8)));
+        assert_eq!(12, beaufort(ms(40.0)));
+    }
+
+    #[test]
+    fn pressure_units_and_precision() {
+        let mut format = PayloadFormat::default();
+        let value = Pressure::new::<pressure::hectopascal>(1013.25);
+        assert_eq!("101325.00", format.pressure(value));
+
+        format.pressure_unit = PressureUnit::InchOfMercury;
+        format.precision.pressure = 2;
+        assert_eq!("29.92", format.pressure(value));
+
+        format.pressure_unit = PressureUnit::MillimeterOfMercury;
+        format.precision.pressure = 0;
+        assert_eq!("760", format.pressure(value));
+    }
+
+    #[test]
+    fn wind_speed_units() {
+        let mut format = PayloadFormat {
+            speed_unit: SpeedUnit::KilometerPerHour,
+            ..Default::default()
+        };
+        assert_eq!("36.0", format.wind_speed(ms(10.0)));
+
+        format.speed_unit = SpeedUnit::Knot;
+        assert_eq!("19.4", format.wind_speed(ms(10.0)));
+
+        format.speed_unit = SpeedUnit::Beaufort;
+        assert_eq!("5", format.wind_speed(ms(10.0)));
+    }
+
+    #[test]
+    fn precipitation_in_inches() {
+        let mut format = PayloadFormat {
+            precipitation_unit: PrecipitationUnit::Inch,
+            ..Default::default()
+        };
+        format.precision.precipitation = 2;
+
+        assert_eq!(
+            "1.00",
+            format.precipitation(Length::new::<length::millimeter>(25.4))
+        );
+    }
+}
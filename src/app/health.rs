@@ -0,0 +1,78 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use slog::Logger;
+
+use crate::app::status::StatusBoard;
+
+/// Maximal ages of the task heartbeat and of the latest observation
+#[derive(Debug, Clone, Copy)]
+pub struct HealthLimits {
+    pub liveness: Duration,
+    pub readiness: Duration,
+    /// Age of the observation itself after which it is reported stale
+    pub staleness: Duration,
+}
+
+pub async fn run_health_server(
+    address: SocketAddr,
+    status: StatusBoard,
+    limits: HealthLimits,
+    logger: Arc<Logger>,
+) -> Result<(), anyhow::Error> {
+    let make_service = make_service_fn(move |_connection| {
+        let status = status.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = route(&request, &status, limits);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)?.serve(make_service);
+    slog::slog_info!(logger, "HTTP status server listening on {}", address);
+
+    server.await?;
+
+    Ok(())
+}
+
+fn route(request: &Request<Body>, status: &StatusBoard, limits: HealthLimits) -> Response<Body> {
+    if request.method() != Method::GET {
+        return plain(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+    }
+
+    match request.uri().path() {
+        "/healthz" => probe(status.is_alive(limits.liveness)),
+        "/readyz" => probe(status.is_ready(limits.readiness)),
+        "/status" => match serde_json::to_string(&status.report(limits.staleness)) {
+            Ok(body) => Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap_or_else(|_| plain(StatusCode::INTERNAL_SERVER_ERROR, "error")),
+            Err(_) => plain(StatusCode::INTERNAL_SERVER_ERROR, "error"),
+        },
+        _ => plain(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+fn probe(ok: bool) -> Response<Body> {
+    if ok {
+        plain(StatusCode::OK, "ok")
+    } else {
+        plain(StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    }
+}
+
+fn plain(code: StatusCode, message: &'static str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = code;
+    response
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+
+use slog::{Drain, Level, OwnedKVList, Record, KV};
+
+use crate::app::logging::LogFormat;
+
+const IDENTIFIER: &str = "outdoor";
+const SYSLOG_SOCKET: &str = "/dev/log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+/// LOG_DAEMON
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Sends RFC 3164 messages to the local syslog socket
+pub struct SyslogDrain {
+    socket: UnixDatagram,
+    format: LogFormat,
+    pid: u32,
+}
+
+impl SyslogDrain {
+    pub fn new(format: LogFormat) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(SYSLOG_SOCKET)?;
+
+        Ok(SyslogDrain {
+            socket,
+            format,
+            pid: std::process::id(),
+        })
+    }
+}
+
+impl Drain for SyslogDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let fields = collect_fields(record, values);
+        let message = format_syslog(
+            record.level(),
+            &record.msg().to_string(),
+            &fields,
+            self.format,
+            self.pid,
+        );
+
+        self.socket.send(message.as_bytes()).map(|_| ())
+    }
+}
+
+/// Sends entries to journald using its native protocol so that fields stay searchable
+pub struct JournaldDrain {
+    socket: UnixDatagram,
+}
+
+impl JournaldDrain {
+    pub fn new() -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(JOURNALD_SOCKET)?;
+
+        Ok(JournaldDrain { socket })
+    }
+}
+
+impl Drain for JournaldDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let fields = collect_fields(record, values);
+        let entry = format_journald(record.level(), &record.msg().to_string(), &fields);
+
+        self.socket.send(&entry).map(|_| ())
+    }
+}
+
+fn collect_fields(record: &Record, values: &OwnedKVList) -> Vec<(String, String)> {
+    let mut collector = FieldCollector(Vec::new());
+    // Serialization into a Vec cannot fail
+    let _ = record.kv().serialize(record, &mut collector);
+    let _ = values.serialize(record, &mut collector);
+
+    collector.0
+}
+
+struct FieldCollector(Vec<(String, String)>);
+
+impl slog::Serializer for FieldCollector {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.push((key.to_string(), val.to_string()));
+        Ok(())
+    }
+}
+
+fn syslog_severity(level: Level) -> u8 {
+    match level {
+        Level::Critical => 2,
+        Level::Error => 3,
+        Level::Warning => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+fn format_syslog(
+    level: Level,
+    message: &str,
+    fields: &[(String, String)],
+    format: LogFormat,
+    pid: u32,
+) -> String {
+    let priority = SYSLOG_FACILITY * 8 + syslog_severity(level);
+    let body = match format {
+        LogFormat::Text => fields.iter().fold(message.to_string(), |acc, (k, v)| {
+            format!("{} {}={}", acc, k, v)
+        }),
+        LogFormat::Json => {
+            let mut object = serde_json::Map::new();
+            object.insert("msg".to_string(), message.into());
+            object.insert("level".to_string(), level.as_short_str().into());
+            for (key, value) in fields {
+                object.insert(key.clone(), value.as_str().into());
+            }
+            serde_json::Value::Object(object).to_string()
+        }
+    };
+
+    format!("<{}>{}[{}]: {}", priority, IDENTIFIER, pid, body)
+}
+
+fn format_journald(level: Level, message: &str, fields: &[(String, String)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    append_journald_field(&mut entry, "MESSAGE", message);
+    append_journald_field(&mut entry, "PRIORITY", &syslog_severity(level).to_string());
+    append_journald_field(&mut entry, "SYSLOG_IDENTIFIER", IDENTIFIER);
+
+    for (key, value) in fields {
+        append_journald_field(&mut entry, &journald_field_name(key), value);
+    }
+
+    entry
+}
+
+fn append_journald_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+/// Journald accepts only uppercase letters, digits and underscores not starting with an underscore
+fn journald_field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .collect();
+
+    name.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit())
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fields() -> Vec<(String, String)> {
+        vec![
+            ("topic".to_string(), "node/weather/thermometer".to_string()),
+            ("status".to_string(), "200".to_string()),
+        ]
+    }
+
+    #[test]
+    fn syslog_text_message() {
+        let line = format_syslog(Level::Error, "Failed", &fields(), LogFormat::Text, 42);
+
+        assert_eq!(
+            "<27>outdoor[42]: Failed topic=node/weather/thermometer status=200",
+            line
+        );
+    }
+
+    #[test]
+    fn syslog_json_message() {
+        let line = format_syslog(Level::Info, "Published", &fields(), LogFormat::Json, 42);
+        let (prefix, json) = line.split_at(line.find('{').unwrap());
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        assert_eq!("<30>outdoor[42]: ", prefix);
+        assert_eq!("Published", value["msg"]);
+        assert_eq!("200", value["status"]);
+    }
+
+    #[test]
+    fn journald_entry() {
+        let entry = format_journald(Level::Warning, "two\nlines", &fields());
+        let text = String::from_utf8_lossy(&entry);
+
+        assert!(entry.starts_with(b"MESSAGE\n\x09\0\0\0\0\0\0\0two\nlines\n"));
+        assert!(text.contains("PRIORITY=4\n"));
+        assert!(text.contains("TOPIC=node/weather/thermometer\n"));
+    }
+
+    #[test]
+    fn journald_field_names_sanitized() {
+        assert_eq!("LATENCY_MS", journald_field_name("latency-ms"));
+        assert_eq!("CODE", journald_field_name("_code"));
+    }
+}
@@ -1,14 +1,120 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::str::FromStr;
+
+use slog::Drain;
+use slog_async::{Async, AsyncGuard};
+use sloggers::file::FileLoggerBuilder;
 use sloggers::terminal::{Destination, TerminalLoggerBuilder};
 use sloggers::types::Severity;
 use sloggers::Build;
 
-pub fn create_logger(verbosity: u8) -> anyhow::Result<slog::Logger> {
-    let mut logger_builder = TerminalLoggerBuilder::new();
-    logger_builder.level(verbosity_to_severity(verbosity));
-    logger_builder.destination(Destination::Stderr);
-    let logger = logger_builder.build()?;
+use crate::app::log_drains::{JournaldDrain, SyslogDrain};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["text", "json"]
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => anyhow::bail!("Unknown log format \"{}\"", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogDestination {
+    Stderr,
+    File,
+    Syslog,
+    Journald,
+}
+
+impl LogDestination {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["stderr", "file", "syslog", "journald"]
+    }
+}
+
+impl FromStr for LogDestination {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stderr" => Ok(LogDestination::Stderr),
+            "file" => Ok(LogDestination::File),
+            "syslog" => Ok(LogDestination::Syslog),
+            "journald" => Ok(LogDestination::Journald),
+            _ => anyhow::bail!("Unknown log destination \"{}\"", s),
+        }
+    }
+}
+
+/// The guard of an asynchronous drain writes out the queued records when dropped,
+/// keep it until the program exits
+pub fn create_logger(
+    verbosity: u8,
+    format: LogFormat,
+    destination: LogDestination,
+    file: Option<&Path>,
+) -> anyhow::Result<(slog::Logger, Option<AsyncGuard>)> {
+    let severity = verbosity_to_severity(verbosity);
+
+    let (logger, guard) = match (destination, format) {
+        (LogDestination::Stderr, LogFormat::Text) => {
+            let mut logger_builder = TerminalLoggerBuilder::new();
+            logger_builder.level(severity);
+            logger_builder.destination(Destination::Stderr);
+            (logger_builder.build()?, None)
+        }
+        (LogDestination::Stderr, LogFormat::Json) => {
+            build_async(slog_json::Json::default(std::io::stderr()), severity)
+        }
+        (LogDestination::File, LogFormat::Text) => {
+            let mut logger_builder = FileLoggerBuilder::new(require_file(file)?);
+            logger_builder.level(severity);
+            (logger_builder.build()?, None)
+        }
+        (LogDestination::File, LogFormat::Json) => {
+            let log_file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(require_file(file)?)?;
+            build_async(slog_json::Json::default(log_file), severity)
+        }
+        (LogDestination::Syslog, format) => build_async(SyslogDrain::new(format)?, severity),
+        (LogDestination::Journald, _) => build_async(JournaldDrain::new()?, severity),
+    };
 
-    Ok(logger)
+    Ok((logger, guard))
+}
+
+fn build_async<D>(drain: D, severity: Severity) -> (slog::Logger, Option<AsyncGuard>)
+where
+    D: Drain<Ok = (), Err = std::io::Error> + Send + 'static,
+{
+    let (drain, guard) = Async::new(drain.fuse()).build_with_guard();
+    // Records logged by tasks outliving the guard are dropped instead of panicking
+    let drain = drain.ignore_res().filter_level(severity.as_level()).fuse();
+
+    (slog::Logger::root(drain, slog::o!()), Some(guard))
+}
+
+fn require_file(file: Option<&Path>) -> anyhow::Result<&Path> {
+    file.ok_or_else(|| anyhow::anyhow!("The file log destination requires --log-file"))
 }
 
 fn verbosity_to_severity(verbosity: u8) -> Severity {
@@ -33,4 +139,26 @@ mod test {
         assert_eq!(Severity::Debug, verbosity_to_severity(3));
         assert_eq!(Severity::Trace, verbosity_to_severity(4));
     }
+
+    #[test]
+    pub fn destinations_parsed() {
+        for variant in LogDestination::variants() {
+            assert!(variant.parse::<LogDestination>().is_ok(), "{}", variant);
+        }
+        assert!("loki".parse::<LogDestination>().is_err());
+    }
+
+    #[test]
+    pub fn formats_parsed() {
+        assert_eq!(LogFormat::Json, "json".parse::<LogFormat>().unwrap());
+        assert_eq!(LogFormat::Text, "text".parse::<LogFormat>().unwrap());
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    pub fn file_destination_requires_path() {
+        let result = create_logger(0, LogFormat::Json, LogDestination::File, None);
+
+        assert!(result.is_err());
+    }
 }
@@ -1,3 +1,11 @@
+pub mod commands;
+pub mod format;
+pub mod health;
+pub mod log_drains;
 pub mod logging;
+pub mod once;
 pub mod publisher;
+pub mod quota;
+pub mod state;
+pub mod status;
 pub mod tasks;
@@ -0,0 +1,285 @@
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use futures_util::stream::StreamExt;
+use rumq_client::{eventloop, MqttOptions, Notification, Request};
+use serde_json::Value;
+use tokio::sync::mpsc::channel;
+use tokio::time;
+use uom::si::{length, pressure, thermodynamic_temperature, velocity};
+
+use crate::app::tasks::create_publish_request;
+use crate::domain::current_weather::CurrentWeather;
+
+/// Longest wait for the broker to acknowledge the published messages
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrintFormat {
+    Table,
+    Json,
+}
+
+impl PrintFormat {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["table", "json"]
+    }
+}
+
+impl FromStr for PrintFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(PrintFormat::Table),
+            "json" => Ok(PrintFormat::Json),
+            _ => anyhow::bail!("Unknown output format \"{}\"", s),
+        }
+    }
+}
+
+/// Message as it would be published
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+impl Message {
+    pub fn from_batches(live: Vec<(String, String)>, retained: Vec<(String, String)>) -> Vec<Self> {
+        let live = live.into_iter().map(|m| (m, false));
+        let retained = retained.into_iter().map(|m| (m, true));
+
+        live.chain(retained)
+            .map(|((topic, payload), retain)| Message {
+                topic,
+                payload,
+                retain,
+            })
+            .collect()
+    }
+}
+
+/// Observation in SI units as the provider reported it
+#[derive(Debug, Serialize)]
+pub struct ObservationSummary {
+    pub provider: Option<String>,
+    pub observed_at: Option<u64>,
+    pub temperature_celsius: f32,
+    pub pressure_pascal: f32,
+    pub station_pressure_pascal: Option<f32>,
+    pub relative_humidity: f32,
+    pub wind_speed_mps: Option<f32>,
+    pub precipitation_mm: Option<f32>,
+    pub condition: Option<String>,
+}
+
+impl From<&CurrentWeather> for ObservationSummary {
+    fn from(weather: &CurrentWeather) -> Self {
+        ObservationSummary {
+            provider: weather.get_source().map(String::from),
+            observed_at: weather
+                .get_observed_at()
+                .map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
+            temperature_celsius: weather
+                .get_temperature()
+                .get::<thermodynamic_temperature::degree_celsius>(),
+            pressure_pascal: weather.get_pressure().get::<pressure::pascal>(),
+            station_pressure_pascal: weather
+                .get_station_pressure()
+                .map(|p| p.get::<pressure::pascal>()),
+            relative_humidity: *weather.get_humidity().as_ref(),
+            wind_speed_mps: weather
+                .get_wind_speed()
+                .map(|v| v.get::<velocity::meter_per_second>()),
+            precipitation_mm: weather
+                .get_precipitation()
+                .map(|l| l.get::<length::millimeter>()),
+            condition: weather.get_condition().map(|c| match &c.description {
+                Some(description) => format!("{} ({})", c.category.name(), description),
+                None => c.category.name().to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    weather: ObservationSummary,
+    messages: &'a [Message],
+}
+
+/// Renders the observation and its messages for the standard output
+pub fn render(
+    weather: &CurrentWeather,
+    messages: &[Message],
+    format: PrintFormat,
+) -> Result<String, anyhow::Error> {
+    let report = Report {
+        weather: weather.into(),
+        messages,
+    };
+
+    match format {
+        PrintFormat::Json => Ok(serde_json::to_string_pretty(&report)?),
+        PrintFormat::Table => {
+            let mut rows = Vec::new();
+            if let Value::Object(fields) = serde_json::to_value(&report.weather)? {
+                for (name, value) in fields {
+                    let value = match value {
+                        Value::Null => continue,
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    rows.push(vec![name, value]);
+                }
+            }
+            let mut output = table(&rows);
+
+            output.push('\n');
+            let mut rows = vec![vec![
+                "TOPIC".to_string(),
+                "PAYLOAD".to_string(),
+                "RETAIN".to_string(),
+            ]];
+            rows.extend(
+                messages
+                    .iter()
+                    .map(|m| vec![m.topic.clone(), m.payload.clone(), m.retain.to_string()]),
+            );
+            output.push_str(&table(&rows));
+
+            Ok(output)
+        }
+    }
+}
+
+/// Left aligned columns separated by two spaces
+pub fn table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| {
+            rows.iter()
+                .filter_map(|row| row.get(c))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut output = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:1$}", cell, width))
+            .collect();
+        output.push_str(cells.join("  ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Connects to the broker, publishes the messages and disconnects
+///
+/// Waits until the broker acknowledges every message, without messages it checks
+/// the connection only.
+pub async fn publish_messages(
+    options: MqttOptions,
+    messages: &[Message],
+) -> Result<(), anyhow::Error> {
+    let (mut requests_tx, requests_rx) = channel(messages.len() + 1);
+    for message in messages {
+        requests_tx
+            .send(create_publish_request(
+                message.payload.clone(),
+                &message.topic,
+                message.retain,
+            ))
+            .await?;
+    }
+
+    let mut event_loop = eventloop(options, requests_rx);
+    let delivery = async {
+        let mut stream = event_loop.connect().await?;
+        let mut acknowledged = 0;
+
+        while acknowledged < messages.len() {
+            match stream.next().await {
+                Some(Notification::Puback(_)) => acknowledged += 1,
+                Some(Notification::Abort(error)) => return Err(error.into()),
+                Some(_) => {}
+                None => anyhow::bail!("MQTT connection closed"),
+            }
+        }
+
+        // Delivered already, the broker closing the connection is not awaited for long
+        requests_tx.send(Request::Disconnect).await?;
+        let closed = async {
+            while let Some(notification) = stream.next().await {
+                if let Notification::Abort(_) = notification {
+                    break;
+                }
+            }
+        };
+        let _ = time::timeout(DISCONNECT_TIMEOUT, closed).await;
+
+        Ok(())
+    };
+
+    match time::timeout(PUBLISH_TIMEOUT, delivery).await {
+        Ok(result) => result,
+        Err(_) => anyhow::bail!(
+            "MQTT broker did not acknowledge in {} s",
+            PUBLISH_TIMEOUT.as_secs()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        Message::from_batches(
+            vec![(
+                "node/weather/thermometer/0:0/temperature".to_string(),
+                "10.15".to_string(),
+            )],
+            vec![(
+                "node/weather/weather/-/stale".to_string(),
+                "false".to_string(),
+            )],
+        )
+    }
+
+    #[test]
+    fn retained_messages_flagged() {
+        let retain: Vec<bool> = messages().iter().map(|m| m.retain).collect();
+
+        assert_eq!(vec![false, true], retain);
+    }
+
+    #[test]
+    fn table_aligned() {
+        let weather = CurrentWeather::new(283.3, 1001.0, 55.1);
+        let output = render(&weather, &messages(), PrintFormat::Table).unwrap();
+
+        assert!(output.contains("temperature_celsius"));
+        assert!(output.contains("node/weather/thermometer/0:0/temperature  10.15    false\n"));
+        assert!(output.contains("node/weather/weather/-/stale              false    true\n"));
+    }
+
+    #[test]
+    fn json_contains_messages() {
+        let weather = CurrentWeather::new(283.3, 1001.0, 55.1);
+        let output = render(&weather, &messages(), PrintFormat::Json).unwrap();
+        let value: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(Value::Bool(true), value["messages"][1]["retain"]);
+        assert_eq!(Value::from(55.1), value["weather"]["relative_humidity"]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::domain::derived::DerivedMetric;
+
 pub trait Topic {
     fn get_value(&self) -> String;
 }
@@ -8,6 +10,9 @@ pub trait PublishingInfo {
     fn get_channel_thermometer(&self) -> &str;
     fn get_channel_barometer(&self) -> &str;
     fn get_channel_hygrometer(&self) -> &str;
+    fn publish_provider_values(&self) -> bool;
+    fn get_derived_metrics(&self) -> &[DerivedMetric];
+    fn get_derived_topic_template(&self) -> &str;
 }
 
 #[derive(Debug)]
@@ -115,3 +120,367 @@ impl<'a> Topic for Humidity<'a> {
         )
     }
 }
+
+/// Property of the node itself, e.g. `node/{device}/info`
+#[derive(Debug)]
+pub struct NodeTopic<'a> {
+    prefix: &'a str,
+    device: &'a str,
+    property: &'a str,
+}
+
+impl<'a> NodeTopic<'a> {
+    pub fn new(prefix: &'a Option<String>, device: &'a str, property: &'a str) -> Self {
+        let prefixed = prefix.as_deref().unwrap_or("");
+        NodeTopic {
+            prefix: prefixed,
+            device,
+            property,
+        }
+    }
+
+    pub fn from_publishing_args(args: &'a dyn PublishingInfo, property: &'a str) -> Self {
+        Self::new(args.get_prefix(), args.get_device_name(), property)
+    }
+}
+
+impl<'a> Topic for NodeTopic<'a> {
+    fn get_value(&self) -> String {
+        format!("{}node/{}/{}", self.prefix, self.device, self.property)
+    }
+}
+
+/// Arbitrary node property, e.g. `node/{device}/weather/-/provider`
+#[derive(Debug)]
+pub struct NodeProperty<'a> {
+    prefix: &'a str,
+    device: &'a str,
+    resource: &'a str,
+    channel: &'a str,
+    property: &'a str,
+}
+
+impl<'a> NodeProperty<'a> {
+    pub fn new(
+        prefix: &'a Option<String>,
+        device: &'a str,
+        resource: &'a str,
+        channel: &'a str,
+        property: &'a str,
+    ) -> Self {
+        let prefixed = prefix.as_deref().unwrap_or("");
+        NodeProperty {
+            prefix: prefixed,
+            device,
+            resource,
+            channel,
+            property,
+        }
+    }
+
+    /// Property of the virtual weather resource which has no channel
+    pub fn weather(args: &'a dyn PublishingInfo, property: &'a str) -> Self {
+        Self::new(
+            args.get_prefix(),
+            args.get_device_name(),
+            "weather",
+            "-",
+            property,
+        )
+    }
+}
+
+impl<'a> Topic for NodeProperty<'a> {
+    fn get_value(&self) -> String {
+        format!(
+            "{}node/{}/{}/{}/{}",
+            self.prefix, self.device, self.resource, self.channel, self.property
+        )
+    }
+}
+
+/// All topics the weather publisher writes to
+#[derive(Debug, Clone)]
+pub struct WeatherTopics {
+    pub temperature: String,
+    pub pressure: String,
+    pub humidity: String,
+    pub wind_speed: String,
+    pub precipitation: String,
+    pub pressure_change: String,
+    pub pressure_tendency: String,
+    pub pressure_tendency_code: String,
+    pub sunrise: String,
+    pub sunset: String,
+    pub day_length: String,
+    pub is_daylight: String,
+    pub condition_id: String,
+    pub condition: String,
+    pub condition_description: String,
+    pub stale: String,
+    pub provider: String,
+    /// Retained description of the node, like Hardwario gateways announce themselves
+    pub info: String,
+    pub uptime: String,
+    pub provider_values: Option<ProviderTopics>,
+    pub derived: Vec<(DerivedMetric, String)>,
+    pub daily: DailyTopics,
+}
+
+impl WeatherTopics {
+    pub fn from_publishing_args(args: &dyn PublishingInfo) -> Self {
+        let provider_values = if args.publish_provider_values() {
+            Some(ProviderTopics::new(
+                args.get_prefix(),
+                args.get_device_name(),
+            ))
+        } else {
+            None
+        };
+
+        WeatherTopics {
+            temperature: Temperature::from_publishing_args(args).get_value(),
+            pressure: Pressure::from_publishing_args(args).get_value(),
+            humidity: Humidity::from_publishing_args(args).get_value(),
+            wind_speed: NodeProperty::weather(args, "wind-speed").get_value(),
+            precipitation: NodeProperty::weather(args, "precipitation").get_value(),
+            pressure_change: NodeProperty::weather(args, "pressure-change").get_value(),
+            pressure_tendency: NodeProperty::weather(args, "pressure-tendency").get_value(),
+            pressure_tendency_code: NodeProperty::weather(args, "pressure-tendency-code")
+                .get_value(),
+            sunrise: NodeProperty::weather(args, "sunrise").get_value(),
+            sunset: NodeProperty::weather(args, "sunset").get_value(),
+            day_length: NodeProperty::weather(args, "day-length").get_value(),
+            is_daylight: NodeProperty::weather(args, "is-daylight").get_value(),
+            condition_id: NodeProperty::weather(args, "condition-id").get_value(),
+            condition: NodeProperty::weather(args, "condition").get_value(),
+            condition_description: NodeProperty::weather(args, "condition-description").get_value(),
+            stale: NodeProperty::weather(args, "stale").get_value(),
+            provider: NodeProperty::weather(args, "provider").get_value(),
+            info: NodeTopic::from_publishing_args(args, "info").get_value(),
+            uptime: NodeProperty::new(
+                args.get_prefix(),
+                args.get_device_name(),
+                "system",
+                "-",
+                "uptime",
+            )
+            .get_value(),
+            provider_values,
+            daily: DailyTopics::new(args.get_prefix(), args.get_device_name()),
+            derived: args
+                .get_derived_metrics()
+                .iter()
+                .map(|metric| (*metric, derived_topic(args, *metric)))
+                .collect(),
+        }
+    }
+}
+
+/// Fills the placeholders of the derived topic template
+pub fn derived_topic(args: &dyn PublishingInfo, metric: DerivedMetric) -> String {
+    args.get_derived_topic_template()
+        .replace("{prefix}", args.get_prefix().as_deref().unwrap_or(""))
+        .replace("{device}", args.get_device_name())
+        .replace("{metric}", metric.name())
+}
+
+/// Problems of the publishing settings that would produce broken topics
+pub fn publishing_problems(args: &dyn PublishingInfo) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let channels = [
+        ("thermometer", args.get_channel_thermometer()),
+        ("barometer", args.get_channel_barometer()),
+        ("hygrometer", args.get_channel_hygrometer()),
+    ];
+    for (device, channel) in channels.iter() {
+        if let Err(e) = check_channel(channel) {
+            problems.push(format!("Channel of the {}: {}", device, e));
+        }
+    }
+
+    let mut topic_parts = vec![
+        ("Device name", args.get_device_name()),
+        ("Derived topic template", args.get_derived_topic_template()),
+    ];
+    if let Some(prefix) = args.get_prefix() {
+        topic_parts.push(("Topic prefix", prefix));
+    }
+    for (name, part) in topic_parts {
+        if let Err(e) = check_wildcards(part) {
+            problems.push(format!("{}: {}", name, e));
+        }
+    }
+
+    let template = args.get_derived_topic_template();
+    if args.get_derived_metrics().len() > 1 && !template.contains("{metric}") {
+        problems.push(format!(
+            "Derived topic template \"{}\" lacks {{metric}}, all derived metrics share a topic",
+            template
+        ));
+    }
+
+    problems
+}
+
+/// Hardwario channels are two numbers separated by a colon, e.g. `0:4`
+pub fn check_channel(channel: &str) -> Result<(), anyhow::Error> {
+    let mut numbers = channel.split(':');
+
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(n), Some(m), None) if n.parse::<u8>().is_ok() && m.parse::<u8>().is_ok() => Ok(()),
+        _ => anyhow::bail!("\"{}\" is not in the N:M format", channel),
+    }
+}
+
+/// Wildcards may be used in subscriptions only, a published topic must not contain them
+pub fn check_wildcards(topic: &str) -> Result<(), anyhow::Error> {
+    match topic.chars().find(|c| *c == '+' || *c == '#') {
+        Some(wildcard) => anyhow::bail!("\"{}\" contains the MQTT wildcard '{}'", topic, wildcard),
+        None => Ok(()),
+    }
+}
+
+/// Statistics of the local day, e.g. `node/{device}/weather/-/temperature-daily-max`
+#[derive(Debug, Clone)]
+pub struct DailyTopics {
+    prefix: Option<String>,
+    device: String,
+}
+
+impl DailyTopics {
+    pub fn new(prefix: &Option<String>, device: &str) -> Self {
+        DailyTopics {
+            prefix: prefix.clone(),
+            device: device.to_string(),
+        }
+    }
+
+    /// Local date the statistics belong to
+    pub fn day(&self) -> String {
+        NodeProperty::new(&self.prefix, &self.device, "weather", "-", "daily-date").get_value()
+    }
+
+    pub fn topic(&self, quantity: &str, statistic: &str) -> String {
+        let property = format!("{}-daily-{}", quantity, statistic);
+        NodeProperty::new(&self.prefix, &self.device, "weather", "-", &property).get_value()
+    }
+}
+
+/// Raw values of individual providers use the provider name as a channel
+#[derive(Debug, Clone)]
+pub struct ProviderTopics {
+    prefix: Option<String>,
+    device: String,
+}
+
+impl ProviderTopics {
+    pub fn new(prefix: &Option<String>, device: &str) -> Self {
+        ProviderTopics {
+            prefix: prefix.clone(),
+            device: device.to_string(),
+        }
+    }
+
+    pub fn temperature(&self, provider: &str) -> String {
+        self.topic("thermometer", provider, "temperature")
+    }
+
+    pub fn pressure(&self, provider: &str) -> String {
+        self.topic("barometer", provider, "pressure")
+    }
+
+    pub fn humidity(&self, provider: &str) -> String {
+        self.topic("hygrometer", provider, "relative-humidity")
+    }
+
+    fn topic(&self, resource: &str, provider: &str, property: &str) -> String {
+        NodeProperty::new(&self.prefix, &self.device, resource, provider, property).get_value()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Args {
+        prefix: Option<String>,
+        template: String,
+    }
+
+    impl PublishingInfo for Args {
+        fn get_prefix(&self) -> &Option<String> {
+            &self.prefix
+        }
+
+        fn get_device_name(&self) -> &str {
+            "weather"
+        }
+
+        fn get_channel_thermometer(&self) -> &str {
+            "0:0"
+        }
+
+        fn get_channel_barometer(&self) -> &str {
+            "0:0"
+        }
+
+        fn get_channel_hygrometer(&self) -> &str {
+            "0:0"
+        }
+
+        fn publish_provider_values(&self) -> bool {
+            false
+        }
+
+        fn get_derived_metrics(&self) -> &[DerivedMetric] {
+            &[DerivedMetric::DewPoint]
+        }
+
+        fn get_derived_topic_template(&self) -> &str {
+            &self.template
+        }
+    }
+
+    #[test]
+    fn channel_format() {
+        assert!(check_channel("0:4").is_ok());
+        assert!(check_channel("12:0").is_ok());
+        assert!(check_channel("0").is_err());
+        assert!(check_channel("a:1").is_err());
+        assert!(check_channel("0:1:2").is_err());
+    }
+
+    #[test]
+    fn wildcards_reported() {
+        let args = Args {
+            prefix: Some("home/#/".to_string()),
+            template: "{prefix}node/{device}/+/{metric}".to_string(),
+        };
+
+        let problems = publishing_problems(&args);
+        assert_eq!(2, problems.len(), "{:?}", problems);
+        assert!(problems[0].starts_with("Derived topic template"));
+        assert!(problems[1].starts_with("Topic prefix"));
+    }
+
+    #[test]
+    fn derived_topic_rendered() {
+        let args = Args {
+            prefix: Some("home/".to_string()),
+            template: "{prefix}node/{device}/weather/-/{metric}".to_string(),
+        };
+
+        let topics = WeatherTopics::from_publishing_args(&args);
+        assert_eq!(
+            vec![(
+                DerivedMetric::DewPoint,
+                "home/node/weather/weather/-/dew-point".to_string()
+            )],
+            topics.derived
+        );
+        assert_eq!("home/node/weather/info", topics.info);
+        assert_eq!("home/node/weather/system/-/uptime", topics.uptime);
+    }
+}
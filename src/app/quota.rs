@@ -0,0 +1,182 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use slog::Logger;
+use tokio::sync::Notify;
+
+use crate::app::status::{ApiUsageReport, StatusBoard};
+use crate::domain::quota::{mask_key, ApiUsage, QuotaBudget};
+
+/// Used share of a budget that deserves a warning
+const WARNING_SHARE: f32 = 0.8;
+
+/// Calls come a little early or late, the budget is not that precise
+const TOLERANCE: Duration = Duration::from_secs(1);
+
+/// Counts the calls of an API key and tells the fetcher how often it may call
+///
+/// Clones share the counts, the publisher persists them with its state after every call.
+#[derive(Clone)]
+pub struct QuotaTracker {
+    usage: Arc<Mutex<ApiUsage>>,
+    last_call: Arc<Mutex<Option<SystemTime>>>,
+    recorded: Arc<Notify>,
+    key: String,
+    budget: QuotaBudget,
+    status: StatusBoard,
+    logger: Logger,
+}
+
+impl QuotaTracker {
+    pub fn new(
+        usage: ApiUsage,
+        api_key: &str,
+        budget: QuotaBudget,
+        status: StatusBoard,
+        logger: Logger,
+    ) -> Self {
+        let tracker = QuotaTracker {
+            usage: Arc::new(Mutex::new(usage)),
+            last_call: Arc::new(Mutex::new(None)),
+            recorded: Arc::new(Notify::new()),
+            key: mask_key(api_key),
+            budget,
+            status,
+            logger,
+        };
+        tracker.report(SystemTime::now());
+
+        tracker
+    }
+
+    /// Counts a request, failed ones count against the quota too
+    pub fn record_call(&self, at: SystemTime) {
+        let (before, after) = {
+            let mut usage = self.lock();
+            let key_usage = usage.key(&self.key);
+            let before = self.budget.used_share(key_usage, at);
+            key_usage.record(at);
+
+            (before, self.budget.used_share(key_usage, at))
+        };
+        *self
+            .last_call
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(at);
+        self.recorded.notify();
+
+        if let (Some(before), Some(after)) = (before, after) {
+            if before < 1.0 && after >= 1.0 {
+                slog::slog_warn!(self.logger, "API call budget exhausted, fetching is slowed down";
+                    "key" => &self.key);
+            } else if before < WARNING_SHARE && after >= WARNING_SHARE {
+                slog::slog_warn!(self.logger, "API call budget nearly used";
+                    "key" => &self.key, "used_percent" => (after * 100.0).round() as u32);
+            }
+        }
+        self.report(at);
+    }
+
+    /// Shortest interval between calls keeping the key within its budget
+    pub fn required_interval(&self, at: SystemTime) -> Duration {
+        match self.lock().get(&self.key) {
+            Some(key_usage) => self.budget.required_interval(key_usage, at),
+            None => Duration::default(),
+        }
+    }
+
+    /// Wait before the next call keeps the key within its budget, zero when it may call now
+    pub fn wait_before_call(&self, at: SystemTime) -> Duration {
+        let last_call = *self
+            .last_call
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let since = match last_call {
+            Some(last_call) => at.duration_since(last_call).unwrap_or_default(),
+            None => return Duration::default(),
+        };
+
+        match self.required_interval(at).saturating_sub(since) {
+            wait if wait > TOLERANCE => wait,
+            _ => Duration::default(),
+        }
+    }
+
+    /// Completes once a call has been counted since the previous wait
+    pub async fn call_recorded(&self) {
+        self.recorded.notified().await
+    }
+
+    /// Copy of the counts for the state file
+    pub fn usage(&self) -> ApiUsage {
+        self.lock().clone()
+    }
+
+    fn report(&self, at: SystemTime) {
+        let (calls_today, calls_this_month) = match self.lock().get(&self.key) {
+            Some(key_usage) => (key_usage.calls_today(at), key_usage.calls_this_month(at)),
+            None => (0, 0),
+        };
+
+        self.status.record_api_usage(ApiUsageReport {
+            key: self.key.clone(),
+            calls_today,
+            calls_this_month,
+            daily_budget: self.budget.daily,
+            monthly_budget: self.budget.monthly,
+            required_interval_secs: self.required_interval(at).as_secs(),
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ApiUsage> {
+        self.usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for QuotaTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaTracker")
+            .field("key", &self.key)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn calls_spread_over_budget() {
+        let budget = QuotaBudget {
+            daily: Some(11),
+            monthly: None,
+        };
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let quota = QuotaTracker::new(
+            ApiUsage::default(),
+            "0123456789abcdef",
+            budget,
+            StatusBoard::new(),
+            logger,
+        );
+        // 2023-11-14 22:13:20 UTC, 1 h 46 min 40 s left of the day
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(Duration::default(), quota.wait_before_call(at));
+
+        quota.record_call(at);
+
+        // 10 calls left in 6100 s
+        let later = at + Duration::from_secs(300);
+        assert_eq!(Duration::from_secs(310), quota.wait_before_call(later));
+        assert_eq!(
+            Duration::default(),
+            quota.wait_before_call(at + Duration::from_secs(600))
+        );
+        assert_eq!(1, quota.usage().get("0123…cdef").unwrap().calls_today(at));
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::domain::daily::DailyStatistics;
+use crate::domain::quota::ApiUsage;
+use crate::domain::trend::PressureHistory;
+
+/// Whatever the publisher has to remember across restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublisherState {
+    #[serde(default)]
+    pub pressure_history: PressureHistory,
+    #[serde(default)]
+    pub daily: DailyStatistics,
+    /// API calls per key, counted against the budgets
+    #[serde(default)]
+    pub api_usage: ApiUsage,
+}
+
+/// JSON file the publisher state survives restarts in
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: &Path) -> Self {
+        StateFile {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Loads the state, a missing file is an empty state
+    pub fn load(&self) -> Result<PublisherState, anyhow::Error> {
+        let body = match fs::read_to_string(&self.path) {
+            Ok(body) => body,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(PublisherState::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Cannot read state file {}", self.path.display()))
+            }
+        };
+
+        serde_json::from_str(&body)
+            .with_context(|| format!("Cannot parse state file {}", self.path.display()))
+    }
+
+    /// Replaces the file at once so that a crash never leaves it half written
+    pub fn save(&self, state: &PublisherState) -> Result<(), anyhow::Error> {
+        let body = serde_json::to_string(state)?;
+        let temporary = self.path.with_extension("tmp");
+
+        fs::write(&temporary, body)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .with_context(|| format!("Cannot write state file {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::trend::TrendWindow;
+    use std::time::{Duration, UNIX_EPOCH};
+    use uom::si::f32::Pressure;
+    use uom::si::pressure;
+
+    #[test]
+    fn state_round_trip() {
+        let path = std::env::temp_dir().join(format!("outdoor-state-{}.json", std::process::id()));
+        let file = StateFile::new(&path);
+        assert!(file
+            .load()
+            .unwrap()
+            .pressure_history
+            .change(TrendWindow::new(
+                Duration::from_secs(1),
+                Duration::from_secs(1)
+            ))
+            .is_none());
+
+        let window = TrendWindow::new(Duration::from_secs(3600), Duration::from_secs(600));
+        let mut state = PublisherState::default();
+        for hour in 0..3 {
+            state.pressure_history.record(
+                UNIX_EPOCH + Duration::from_secs(hour * 3600),
+                Pressure::new::<pressure::hectopascal>(1000.0 + hour as f32),
+                window,
+            );
+        }
+        file.save(&state).unwrap();
+
+        let loaded = file.load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let change = loaded.pressure_history.change(window).unwrap();
+        assert!((1.0 - change.get::<pressure::hectopascal>()).abs() < 0.001);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use uom::si::{pressure, thermodynamic_temperature};
+
+use crate::domain::current_weather::CurrentWeather;
+use crate::domain::validation::Rejection;
+use crate::http_cache::CacheOutcome;
+
+/// Shared runtime state of the daemon tasks
+///
+/// Tasks record their progress here and the HTTP status server reads it.
+#[derive(Clone, Default)]
+pub struct StatusBoard {
+    inner: Arc<Mutex<Status>>,
+}
+
+#[derive(Default)]
+struct Status {
+    fetcher_tick: Option<Instant>,
+    publisher_busy_since: Option<Instant>,
+    observation: Option<Observation>,
+    last_error: Option<FetchError>,
+    rejections: BTreeMap<&'static str, u64>,
+    api_usage: Option<ApiUsageReport>,
+    cache_outcomes: BTreeMap<&'static str, u64>,
+    mqtt_connected: bool,
+}
+
+struct Observation {
+    received: Instant,
+    received_at: SystemTime,
+    weather: CurrentWeather,
+}
+
+struct FetchError {
+    occurred_at: SystemTime,
+    message: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatusReport {
+    pub mqtt_connected: bool,
+    pub weather: Option<WeatherReport>,
+    pub last_error: Option<ErrorReport>,
+    /// Implausible observations dropped per quantity
+    pub rejections: BTreeMap<&'static str, u64>,
+    pub api_usage: Option<ApiUsageReport>,
+    /// Weather requests served from the HTTP cache, revalidated or missed
+    pub http_cache: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WeatherReport {
+    pub provider: Option<String>,
+    pub observed_at: Option<u64>,
+    pub received_at: u64,
+    pub age_secs: u64,
+    pub temperature_celsius: f32,
+    pub pressure_pascal: f32,
+    pub relative_humidity: f32,
+    /// The observation is older than the configured maximum age
+    pub stale: bool,
+}
+
+/// Calls of the API key against its budget
+#[derive(Serialize, Debug, Clone)]
+pub struct ApiUsageReport {
+    /// Masked API key
+    pub key: String,
+    pub calls_today: u32,
+    pub calls_this_month: u32,
+    pub daily_budget: Option<u32>,
+    pub monthly_budget: Option<u32>,
+    /// Shortest interval between calls keeping the key within the budget
+    pub required_interval_secs: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorReport {
+    pub occurred_at: u64,
+    pub message: String,
+}
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_fetcher_tick(&self) {
+        self.lock().fetcher_tick = Some(Instant::now());
+    }
+
+    /// The publisher started handling an event, or went back to waiting when not `busy`
+    pub fn set_publisher_busy(&self, busy: bool) {
+        self.lock().publisher_busy_since = if busy { Some(Instant::now()) } else { None };
+    }
+
+    pub fn record_weather(&self, weather: &CurrentWeather) {
+        self.lock().observation = Some(Observation {
+            received: Instant::now(),
+            received_at: SystemTime::now(),
+            weather: weather.clone(),
+        });
+    }
+
+    pub fn record_error(&self, error: &anyhow::Error) {
+        self.lock().last_error = Some(FetchError {
+            occurred_at: SystemTime::now(),
+            message: format!("{:#}", error),
+        });
+    }
+
+    pub fn record_rejection(&self, rejection: &Rejection) {
+        *self.lock().rejections.entry(rejection.kind()).or_insert(0) += 1;
+    }
+
+    pub fn record_cache_outcome(&self, outcome: CacheOutcome) {
+        *self
+            .lock()
+            .cache_outcomes
+            .entry(outcome.name())
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_api_usage(&self, usage: ApiUsageReport) {
+        self.lock().api_usage = Some(usage);
+    }
+
+    pub fn set_mqtt_connected(&self, connected: bool) {
+        self.lock().mqtt_connected = connected;
+    }
+
+    /// The weather fetcher has ticked recently enough and the publisher is not stuck
+    ///
+    /// A publisher stuck on a full request queue also means the MQTT loop stopped
+    /// taking requests.
+    pub fn is_alive(&self, max_tick_age: Duration) -> bool {
+        self.is_alive_at(Instant::now(), max_tick_age)
+    }
+
+    /// MQTT is connected and an observation is not older than `max_age`
+    pub fn is_ready(&self, max_age: Duration) -> bool {
+        self.is_ready_at(Instant::now(), max_age)
+    }
+
+    pub fn report(&self, stale_after: Duration) -> StatusReport {
+        let status = self.lock();
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+
+        StatusReport {
+            mqtt_connected: status.mqtt_connected,
+            weather: status.observation.as_ref().map(|o| WeatherReport {
+                provider: o.weather.get_source().map(String::from),
+                observed_at: o.weather.get_observed_at().map(unix_timestamp),
+                received_at: unix_timestamp(o.received_at),
+                age_secs: now.saturating_duration_since(o.received).as_secs(),
+                temperature_celsius: o
+                    .weather
+                    .get_temperature()
+                    .get::<thermodynamic_temperature::degree_celsius>(),
+                pressure_pascal: o.weather.get_pressure().get::<pressure::pascal>(),
+                relative_humidity: *o.weather.get_humidity().as_ref(),
+                stale: o
+                    .weather
+                    .age(wall_now)
+                    .unwrap_or_else(|| wall_now.duration_since(o.received_at).unwrap_or_default())
+                    > stale_after,
+            }),
+            last_error: status.last_error.as_ref().map(|e| ErrorReport {
+                occurred_at: unix_timestamp(e.occurred_at),
+                message: e.message.clone(),
+            }),
+            rejections: status.rejections.clone(),
+            api_usage: status.api_usage.clone(),
+            http_cache: status.cache_outcomes.clone(),
+        }
+    }
+
+    fn is_alive_at(&self, now: Instant, max_tick_age: Duration) -> bool {
+        let status = self.lock();
+        let ticking = match status.fetcher_tick {
+            Some(tick) => now.saturating_duration_since(tick) <= max_tick_age,
+            None => false,
+        };
+        let publishing = match status.publisher_busy_since {
+            Some(since) => now.saturating_duration_since(since) <= max_tick_age,
+            None => true,
+        };
+
+        ticking && publishing
+    }
+
+    fn is_ready_at(&self, now: Instant, max_age: Duration) -> bool {
+        let status = self.lock();
+        let fresh = match &status.observation {
+            Some(o) => now.saturating_duration_since(o.received) <= max_age,
+            None => false,
+        };
+
+        status.mqtt_connected && fresh
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        // A panicking task must not take the status server down with it
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn not_alive_before_first_tick() {
+        let board = StatusBoard::new();
+
+        assert!(!board.is_alive(MINUTE));
+    }
+
+    #[test]
+    fn alive_after_tick() {
+        let board = StatusBoard::new();
+        board.record_fetcher_tick();
+
+        assert!(board.is_alive(MINUTE));
+        assert!(!board.is_alive_at(Instant::now() + 2 * MINUTE, MINUTE));
+    }
+
+    #[test]
+    fn not_alive_with_stuck_publisher() {
+        let board = StatusBoard::new();
+        board.record_fetcher_tick();
+        board.set_publisher_busy(true);
+
+        assert!(board.is_alive(MINUTE));
+        assert!(!board.is_alive_at(Instant::now() + 2 * MINUTE, MINUTE));
+
+        board.record_fetcher_tick();
+        board.set_publisher_busy(false);
+        assert!(board.is_alive(MINUTE));
+    }
+
+    #[test]
+    fn ready_requires_mqtt_and_fresh_weather() {
+        let board = StatusBoard::new();
+        assert!(!board.is_ready(MINUTE));
+
+        board.record_weather(&CurrentWeather::new(283.3, 1001.0, 55.1));
+        assert!(!board.is_ready(MINUTE), "MQTT is not connected yet");
+
+        board.set_mqtt_connected(true);
+        assert!(board.is_ready(MINUTE));
+        assert!(!board.is_ready_at(Instant::now() + 2 * MINUTE, MINUTE));
+    }
+
+    #[test]
+    fn report_contains_last_error() {
+        let board = StatusBoard::new();
+        board.record_error(&anyhow::anyhow!("Error code 401"));
+
+        let report = board.report(MINUTE);
+        assert_eq!("Error code 401", report.last_error.unwrap().message);
+        assert!(report.weather.is_none());
+    }
+
+    #[test]
+    fn old_observation_reported_stale() {
+        let board = StatusBoard::new();
+        let observed_at = SystemTime::now() - 2 * MINUTE;
+        board.record_weather(
+            &CurrentWeather::new(283.3, 1001.0, 55.1).with_observed_at(observed_at),
+        );
+
+        assert!(board.report(MINUTE).weather.unwrap().stale);
+        assert!(!board.report(3 * MINUTE).weather.unwrap().stale);
+    }
+
+    #[test]
+    fn rejections_counted_per_quantity() {
+        let board = StatusBoard::new();
+        board.record_rejection(&Rejection::Humidity(132.0));
+        board.record_rejection(&Rejection::Humidity(-20.0));
+        board.record_rejection(&Rejection::Temperature(-120.0));
+
+        let report = board.report(MINUTE);
+        assert_eq!(Some(&2), report.rejections.get("humidity"));
+        assert_eq!(Some(&1), report.rejections.get("temperature"));
+    }
+}
@@ -1,18 +1,33 @@
-use std::future::Future;
+use std::fmt::Display;
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use chrono_tz::Tz;
 use futures_util::stream::StreamExt;
 use rumq_client::{MqttEventLoop, Notification, Publish, QoS, Request};
 use slog::Logger;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time;
-use tokio::time::Duration;
-use uom::si::pressure;
+use tokio::time::{Duration, Instant};
 
-use crate::app::publisher::{Humidity, Pressure, Temperature, Topic};
-use crate::arguments::Units;
+use crate::app::commands::{CommandHandler, FetcherCommand};
+use crate::app::format::PayloadFormat;
+use crate::app::publisher::WeatherTopics;
+use crate::app::quota::QuotaTracker;
+use crate::app::state::{PublisherState, StateFile};
+use crate::app::status::StatusBoard;
+use crate::domain::cadence::AdaptiveSchedule;
 use crate::domain::current_weather::CurrentWeather;
+use crate::domain::daily::{local_date, next_midnight, DailyStatistics};
+use crate::domain::derived::DerivedValue;
+use crate::domain::freshness::Freshness;
 use crate::domain::interfaces::WeatherClient;
+use crate::domain::sun::Daylight;
+use crate::domain::trend::TrendWindow;
+use crate::domain::validation::Validator;
+
+/// Topics and payloads of a publishing batch
+pub type Messages = Vec<(String, String)>;
 
 pub enum OnErrorBehaviour {
     Continue,
@@ -26,7 +41,12 @@ where
     channel: Sender<CurrentWeather>,
     api_client: T,
     logger: Arc<Logger>,
+    status: StatusBoard,
     error_behaviour: OnErrorBehaviour,
+    validator: Option<Validator>,
+    commands: Option<Receiver<FetcherCommand>>,
+    schedule: Option<AdaptiveSchedule>,
+    quota: Option<QuotaTracker>,
 }
 
 impl<T> WeatherFetcherBuilder<T>
@@ -37,12 +57,18 @@ where
         channel: Sender<CurrentWeather>,
         api_client: T,
         logger: Arc<Logger>,
+        status: StatusBoard,
     ) -> WeatherFetcherBuilder<T> {
         WeatherFetcherBuilder {
             channel,
             api_client,
             logger,
+            status,
             error_behaviour: OnErrorBehaviour::Continue,
+            validator: None,
+            commands: None,
+            schedule: None,
+            quota: None,
         }
     }
 
@@ -51,27 +77,119 @@ where
         self.error_behaviour = behaviour;
     }
 
+    /// Drops implausible observations instead of publishing them
+    pub fn set_validator(&mut self, validator: Validator) {
+        self.validator = Some(validator);
+    }
+
+    /// Accepts refresh and interval commands besides the regular schedule
+    pub fn set_commands(&mut self, commands: Receiver<FetcherCommand>) {
+        self.commands = Some(commands);
+    }
+
+    /// Fetches shortly after the provider is expected to update, the period is the longest wait
+    pub fn set_adaptive_schedule(&mut self, schedule: AdaptiveSchedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Skips fetches that would exceed the API call budget
+    pub fn set_quota(&mut self, quota: QuotaTracker) {
+        self.quota = Some(quota);
+    }
+
     pub async fn build_task(mut self, period: Duration) -> Result<(), anyhow::Error> {
+        let mut period = period;
         let mut interval = time::interval(period);
 
         loop {
-            interval.tick().await;
+            let command = match self.commands.as_mut() {
+                Some(commands) => tokio::select! {
+                    _ = interval.tick() => None,
+                    command = commands.recv() => Some(command),
+                },
+                None => {
+                    interval.tick().await;
+                    None
+                }
+            };
+            match command {
+                Some(None) => {
+                    // Nobody sends commands any more, the schedule alone drives the fetcher
+                    self.commands = None;
+                    continue;
+                }
+                Some(Some(FetcherCommand::SetInterval(new_period))) => {
+                    slog::slog_info!(self.logger, "Fetch interval changed";
+                        "interval_secs" => new_period.as_secs());
+                    period = new_period;
+                    interval = time::interval_at(Instant::now() + period, period);
+                    continue;
+                }
+                Some(Some(FetcherCommand::Refresh)) => {
+                    slog::slog_info!(self.logger, "Fetching weather on request");
+                }
+                None => {}
+            }
+            self.status.record_fetcher_tick();
+
+            if let Some(quota) = &self.quota {
+                // Refresh commands count against the budget too
+                let wait = quota.wait_before_call(SystemTime::now());
+                if wait > Duration::default() {
+                    slog::slog_info!(self.logger, "Fetch postponed to keep the API call budget";
+                        "wait_secs" => wait.as_secs(), "requested" => command.is_some());
+                    interval = time::interval_at(Instant::now() + wait, period);
+                    continue;
+                }
+            }
 
+            let started = Instant::now();
             let result = self.api_client.get_current_weather().await;
+            let latency_ms = started.elapsed().as_millis() as u64;
+
             match result {
                 Err(e) => {
+                    self.status.record_error(&e);
+                    let error = format!("{:#}", e);
+
                     match self.error_behaviour {
                         OnErrorBehaviour::Abort => {
-                            slog::slog_error!(self.logger, "{:#?}, aborting.", e);
+                            slog::slog_error!(self.logger, "Weather request failed, aborting";
+                                "error" => error, "latency_ms" => latency_ms);
 
                             return Err(e);
                         }
                         OnErrorBehaviour::Continue => {
-                            slog::slog_error!(self.logger, "{:#?}.", e);
+                            slog::slog_error!(self.logger, "Weather request failed";
+                                "error" => error, "latency_ms" => latency_ms);
                         }
                     };
                 }
                 Ok(v) => {
+                    let v = match &mut self.validator {
+                        Some(validator) => match validator.check(v, SystemTime::now()) {
+                            Ok(v) => v,
+                            Err(rejection) => {
+                                slog::slog_warn!(self.logger, "Implausible weather rejected";
+                                    "reason" => rejection.to_string(), "latency_ms" => latency_ms);
+                                self.status.record_rejection(&rejection);
+                                continue;
+                            }
+                        },
+                        None => v,
+                    };
+                    slog::slog_info!(self.logger, "Weather fetched"; "latency_ms" => latency_ms);
+                    if let Some(schedule) = &mut self.schedule {
+                        let now = SystemTime::now();
+                        schedule.cadence.record(v.get_observed_at().unwrap_or(now));
+                        if let Some(wait) = schedule.next_fetch(now, period) {
+                            slog::slog_debug!(self.logger, "Next fetch scheduled";
+                                "wait_secs" => wait.as_secs(),
+                                "cadence_secs" => schedule.cadence.period().map(|p| p.as_secs()));
+                            interval = time::interval_at(Instant::now() + wait, period);
+                        }
+                    }
+                    self.status.record_weather(&v);
                     self.channel.send(v).await?;
                 }
             };
@@ -81,14 +199,18 @@ where
 
 pub async fn run_mqtt_loop(
     mut event_loop: MqttEventLoop,
+    mut commands: CommandHandler,
     logger: Arc<Logger>,
+    status: StatusBoard,
 ) -> Result<(), anyhow::Error> {
     let mut stream = event_loop.connect().await?;
+    status.set_mqtt_connected(true);
 
     while let Some(notification) = stream.next().await {
         match notification {
-            Notification::Publish(_p) => {
-                slog::slog_debug!(logger, "Publih = {:?}", _p);
+            Notification::Publish(p) => {
+                slog::slog_debug!(logger, "Publish = {:?}", p);
+                commands.handle(&p, &logger);
             }
             Notification::Puback(_pid) => {
                 slog::slog_debug!(logger, "Puback = {:?}", _pid);
@@ -106,56 +228,765 @@ pub async fn run_mqtt_loop(
                 slog::slog_debug!(logger, "Unsuback = {:?}", _usa);
             }
             Notification::Abort(error) => {
-                slog::slog_debug!(logger, "Requests abort");
+                slog::slog_error!(logger, "MQTT connection aborted"; "error" => ?error);
+                status.set_mqtt_connected(false);
                 return Err(error.into());
             }
         }
     }
 
+    status.set_mqtt_connected(false);
     Ok(())
 }
 
-pub fn create_mqtt_publisher(
+/// Logs the requests instead of sending them to the MQTT broker
+pub async fn run_log_sink(
+    mut requests_rx: Receiver<Request>,
+    logger: Arc<Logger>,
+) -> Result<(), anyhow::Error> {
+    while let Some(request) = requests_rx.recv().await {
+        match request {
+            Request::Publish(publish) => {
+                slog::slog_info!(logger, "Dry run publish";
+                    "topic" => &publish.topic_name,
+                    "payload" => String::from_utf8_lossy(&publish.payload).into_owned(),
+                    "qos" => ?publish.qos,
+                    "retain" => publish.retain);
+            }
+            other => {
+                slog::slog_debug!(logger, "Dry run request"; "request" => ?other);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Observations the publisher remembers to publish trends
+pub struct PublisherHistory {
+    pub state: PublisherState,
+    pub state_file: Option<StateFile>,
+    pub trend_window: TrendWindow,
+    /// Zone of the local day, the provider UTC offset is used when unset
+    pub timezone: Option<Tz>,
+    /// Latest UTC offset reported by the provider
+    pub utc_offset: Option<i32>,
+    pub daylight: Daylight,
+    pub freshness: Freshness,
+    /// API call counts saved with the state
+    pub quota: Option<QuotaTracker>,
+    /// Start of the service, reported as the node uptime
+    pub started: Instant,
+}
+
+impl PublisherHistory {
+    pub fn record(&mut self, weather: &CurrentWeather, format: &PayloadFormat, now: SystemTime) {
+        let observed_at = weather.get_observed_at().unwrap_or(now);
+        let pressure = format.pressure.pressure(weather);
+        if let Some(pressure) = pressure {
+            self.state
+                .pressure_history
+                .record(observed_at, pressure, self.trend_window);
+        }
+
+        if let Some(offset) = weather.get_utc_offset() {
+            self.utc_offset = Some(offset);
+        }
+        let day = local_date(observed_at, self.timezone, self.utc_offset);
+        self.state.daily.record(observed_at, day, weather, pressure);
+
+        self.daylight.update(weather);
+        self.freshness.update(weather, now);
+    }
+
+    /// Start of the next local day, when the daily statistics roll over
+    pub fn next_midnight(&self, now: SystemTime) -> SystemTime {
+        next_midnight(now, self.timezone, self.utc_offset)
+    }
+
+    /// Starts the statistics of the day at `now`, returns whether the day changed
+    pub fn roll_over(&mut self, now: SystemTime) -> bool {
+        let day = local_date(now, self.timezone, self.utc_offset);
+        self.state.daily.roll_over(day)
+    }
+
+    fn save(&mut self, logger: &Logger) {
+        if let Some(quota) = &self.quota {
+            self.state.api_usage = quota.usage();
+        }
+        if let Some(file) = &self.state_file {
+            if let Err(e) = file.save(&self.state) {
+                slog::slog_warn!(logger, "Publisher state not saved"; "error" => format!("{:#}", e));
+            }
+        }
+    }
+}
+
+pub async fn create_mqtt_publisher(
     mut weather_rx: Receiver<CurrentWeather>,
-    temperature: Temperature,
-    mut temperature_tx: Sender<Request>,
-    pressure: Pressure,
-    humidity: Humidity,
-    units: Units,
+    topics: WeatherTopics,
+    mut requests_tx: Sender<Request>,
+    format: PayloadFormat,
+    mut history: PublisherHistory,
+    status: StatusBoard,
     logger: Arc<Logger>,
-) -> impl Future<Output = ()> + 'static {
-    let t_temp = temperature.get_value();
-    let t_pressure = pressure.get_value();
-    let t_humidity = humidity.get_value();
-
-    let mut pub_pressure_tx = temperature_tx.clone();
-    let mut pub_humidity_tx = temperature_tx.clone();
-
-    async move {
-        while let Some(v) = weather_rx.recv().await {
-            let temperature: f32 = units.convert_temperature(*v.get_temperature());
-            let r_temp = temperature_tx.send(create_publish_request(
-                format!("{0:.2}", temperature),
-                &t_temp,
-            ));
-            let r_pressure = pub_pressure_tx.send(create_publish_request(
-                format!("{0:.2}", v.get_pressure().get::<pressure::pascal>()),
-                &t_pressure,
+) {
+    let mut flipped_at = SystemTime::UNIX_EPOCH;
+    let mut rolled_at = SystemTime::UNIX_EPOCH;
+    let mut stale = false;
+    let quota = history.quota.clone();
+    loop {
+        let midnight = history.next_midnight(SystemTime::now().max(rolled_at));
+        let transition = history
+            .daylight
+            .next_transition(SystemTime::now().max(flipped_at));
+        let stale_at = if stale {
+            None
+        } else {
+            Some(history.freshness.stale_at())
+        };
+
+        status.set_publisher_busy(false);
+        tokio::select! {
+            received = weather_rx.recv() => {
+                status.set_publisher_busy(true);
+                let v = match received {
+                    Some(v) => v,
+                    None => break,
+                };
+                let now = SystemTime::now();
+                history.record(&v, &format, now);
+                history.save(&logger);
+
+                stale = history.freshness.is_stale(now);
+                if stale {
+                    slog::slog_warn!(logger, "Received weather is stale";
+                        "age_secs" => history.freshness.age(now).as_secs());
+                }
+
+                let (messages, retained) = observation_messages(&v, &history, &topics, &format, now);
+                publish(&mut requests_tx, messages, false, &logger).await;
+                publish(&mut requests_tx, retained, true, &logger).await;
+            }
+            _ = delay_until_time(stale_at) => {
+                status.set_publisher_busy(true);
+                let now = SystemTime::now();
+                if history.freshness.is_stale(now) {
+                    stale = true;
+                    slog::slog_warn!(logger, "No fresh weather, published values are stale";
+                        "age_secs" => history.freshness.age(now).as_secs());
+                    publish(&mut requests_tx, vec![stale_message(true, &topics)], true, &logger).await;
+                }
+            }
+            _ = delay_until_time(transition) => {
+                status.set_publisher_busy(true);
+                // The transition itself decides, the timer may fire a bit early
+                if let Some(at) = transition {
+                    flipped_at = at;
+                    let message = daylight_message(&history.daylight, at, &topics);
+                    publish(&mut requests_tx, message.into_iter().collect(), true, &logger).await;
+                }
+            }
+            _ = call_recorded(quota.as_ref()) => {
+                status.set_publisher_busy(true);
+                // The counts must survive a restart during a provider outage too
+                history.save(&logger);
+            }
+            _ = delay_until_time(Some(midnight)) => {
+                status.set_publisher_busy(true);
+                // Without observations after midnight the statistics of yesterday would stay
+                rolled_at = midnight;
+                if history.roll_over(midnight) {
+                    history.save(&logger);
+                    let messages = rollover_messages(&history.state.daily, &topics);
+                    publish(&mut requests_tx, messages, true, &logger).await;
+                }
+            }
+        }
+    }
+}
+
+/// Messages of an observation already recorded in the history, plain and retained ones
+pub fn observation_messages(
+    weather: &CurrentWeather,
+    history: &PublisherHistory,
+    topics: &WeatherTopics,
+    format: &PayloadFormat,
+    now: SystemTime,
+) -> (Messages, Messages) {
+    let mut messages = weather_messages(weather, topics, format);
+    messages.extend(trend_messages(history, topics, format));
+    messages.extend(sun_messages(&history.daylight, now, topics));
+    messages.push(uptime_message(history.started, Instant::now(), topics));
+
+    // Retained so that dashboards show the day so far right after subscribing
+    let mut retained = daily_messages(&history.state.daily, topics, format);
+    retained.extend(daylight_message(&history.daylight, now, topics));
+    retained.push(stale_message(history.freshness.is_stale(now), topics));
+    retained.push(info_message(weather, topics));
+
+    (messages, retained)
+}
+
+/// Sleeps until the wall clock time, forever without one
+async fn delay_until_time(at: Option<SystemTime>) {
+    match at {
+        Some(at) => {
+            let remaining = at.duration_since(SystemTime::now()).unwrap_or_default();
+            time::delay_for(remaining).await
+        }
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Waits for an API call counted against the budget, forever without a budget
+async fn call_recorded(quota: Option<&QuotaTracker>) {
+    match quota {
+        Some(quota) => quota.call_recorded().await,
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Retained so that consumers stop trusting the last values when no fresh ones come
+pub fn stale_message(stale: bool, topics: &WeatherTopics) -> (String, String) {
+    (topics.stale.clone(), stale.to_string())
+}
+
+/// Firmware name and version of the virtual node and the location it reports for
+pub fn info_message(weather: &CurrentWeather, topics: &WeatherTopics) -> (String, String) {
+    let info = serde_json::json!({
+        "firmware": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "location": weather.get_location_name(),
+    });
+
+    (topics.info.clone(), info.to_string())
+}
+
+/// Seconds since the service started
+pub fn uptime_message(started: Instant, now: Instant, topics: &WeatherTopics) -> (String, String) {
+    // Monotonic, wall clock adjustments do not skew it
+    let uptime = now.saturating_duration_since(started);
+
+    (topics.uptime.clone(), uptime.as_secs().to_string())
+}
+
+/// Sunrise and sunset as Unix timestamps and the day length in seconds
+pub fn sun_messages(
+    daylight: &Daylight,
+    now: SystemTime,
+    topics: &WeatherTopics,
+) -> Vec<(String, String)> {
+    let times = match daylight.times(now) {
+        Some(times) => times,
+        None => return Vec::new(),
+    };
+    let timestamp = |t: SystemTime| {
+        t.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string()
+    };
+
+    vec![
+        (topics.sunrise.clone(), timestamp(times.sunrise)),
+        (topics.sunset.clone(), timestamp(times.sunset)),
+        (
+            topics.day_length.clone(),
+            times.day_length().as_secs().to_string(),
+        ),
+    ]
+}
+
+pub fn daylight_message(
+    daylight: &Daylight,
+    at: SystemTime,
+    topics: &WeatherTopics,
+) -> Option<(String, String)> {
+    daylight
+        .is_daylight(at)
+        .map(|is_daylight| (topics.is_daylight.clone(), is_daylight.to_string()))
+}
+
+async fn publish(
+    requests_tx: &mut Sender<Request>,
+    messages: Vec<(String, String)>,
+    retain: bool,
+    logger: &Logger,
+) {
+    for (topic, payload) in messages {
+        let result = requests_tx
+            .send(create_publish_request(payload.clone(), &topic, retain))
+            .await;
+        log_publish_result(logger, &topic, &payload, result);
+    }
+}
+
+/// Minimum, maximum and mean of the local day so far
+pub fn daily_messages(
+    daily: &DailyStatistics,
+    topics: &WeatherTopics,
+    format: &PayloadFormat,
+) -> Vec<(String, String)> {
+    let temperature = daily
+        .temperature()
+        .into_iter()
+        .map(|(s, v)| (topics.daily.topic("temperature", s), format.temperature(v)));
+    let humidity = daily.humidity().into_iter().map(|(s, v)| {
+        (
+            topics.daily.topic("relative-humidity", s),
+            format.humidity(v),
+        )
+    });
+    let pressure = daily
+        .pressure()
+        .into_iter()
+        .map(|(s, v)| (topics.daily.topic("pressure", s), format.pressure(v)));
+
+    let day = daily.day().map(|day| (topics.daily.day(), day.to_string()));
+
+    day.into_iter()
+        .chain(temperature)
+        .chain(humidity)
+        .chain(pressure)
+        .collect()
+}
+
+/// The date of a new day, the retained statistics of the previous one are cleared
+pub fn rollover_messages(daily: &DailyStatistics, topics: &WeatherTopics) -> Messages {
+    let cleared = ["temperature", "relative-humidity", "pressure"]
+        .iter()
+        .flat_map(|quantity| {
+            ["min", "max", "mean"]
+                .iter()
+                .map(move |statistic| (topics.daily.topic(quantity, statistic), String::new()))
+        });
+    let day = daily.day().map(|day| (topics.daily.day(), day.to_string()));
+
+    day.into_iter().chain(cleared).collect()
+}
+
+/// Pressure change over the trend window, nothing until the history covers it
+pub fn trend_messages(
+    history: &PublisherHistory,
+    topics: &WeatherTopics,
+    format: &PayloadFormat,
+) -> Vec<(String, String)> {
+    let pressure_history = &history.state.pressure_history;
+    let mut messages = Vec::new();
+
+    if let Some(change) = pressure_history.change(history.trend_window) {
+        messages.push((topics.pressure_change.clone(), format.pressure(change)));
+    }
+    if let Some(tendency) = pressure_history.tendency(history.trend_window) {
+        messages.push((
+            topics.pressure_tendency.clone(),
+            tendency.name().to_string(),
+        ));
+    }
+    if let Some(code) = pressure_history.tendency_code(history.trend_window) {
+        messages.push((topics.pressure_tendency_code.clone(), code.to_string()));
+    }
+
+    messages
+}
+
+/// Topics and payloads published for a single observation
+pub fn weather_messages(
+    weather: &CurrentWeather,
+    topics: &WeatherTopics,
+    format: &PayloadFormat,
+) -> Vec<(String, String)> {
+    let humidity: &f32 = weather.get_humidity().as_ref();
+
+    let mut messages = vec![(
+        topics.temperature.clone(),
+        format.temperature(*weather.get_temperature()),
+    )];
+    if let Some(pressure) = format.pressure.pressure(weather) {
+        messages.push((topics.pressure.clone(), format.pressure(pressure)));
+    }
+    messages.push((topics.humidity.clone(), format.humidity(*humidity)));
+
+    if let Some(wind_speed) = weather.get_wind_speed() {
+        messages.push((topics.wind_speed.clone(), format.wind_speed(*wind_speed)));
+    }
+    if let Some(precipitation) = weather.get_precipitation() {
+        messages.push((
+            topics.precipitation.clone(),
+            format.precipitation(*precipitation),
+        ));
+    }
+
+    if let Some(condition) = weather.get_condition() {
+        if let Some(id) = condition.id {
+            messages.push((topics.condition_id.clone(), id.to_string()));
+        }
+        messages.push((
+            topics.condition.clone(),
+            condition.category.name().to_string(),
+        ));
+        if let Some(description) = &condition.description {
+            messages.push((topics.condition_description.clone(), description.clone()));
+        }
+    }
+
+    if let Some(source) = weather.get_source() {
+        messages.push((topics.provider.clone(), source.to_string()));
+    }
+
+    for (metric, topic) in &topics.derived {
+        let payload = match metric.value(weather) {
+            Some(DerivedValue::Temperature(t)) => format.temperature(t),
+            Some(DerivedValue::Density(d)) => format.density(d),
+            Some(DerivedValue::Index(i)) => format!("{0:.1$}", i, format.precision.temperature),
+            None => continue,
+        };
+        messages.push((topic.clone(), payload));
+    }
+
+    if let Some(provider_topics) = &topics.provider_values {
+        for contribution in weather.get_contributions() {
+            let provider = match contribution.get_source() {
+                Some(source) => source,
+                None => continue,
+            };
+            let humidity: &f32 = contribution.get_humidity().as_ref();
+
+            messages.push((
+                provider_topics.temperature(provider),
+                format.temperature(*contribution.get_temperature()),
             ));
-            let humidity_value: &f32 = v.get_humidity().as_ref();
-            let r_humidity = pub_humidity_tx.send(create_publish_request(
-                format!("{0:.1}", humidity_value),
-                &t_humidity,
+            if let Some(pressure) = format.pressure.pressure(contribution) {
+                messages.push((
+                    provider_topics.pressure(provider),
+                    format.pressure(pressure),
+                ));
+            }
+            messages.push((
+                provider_topics.humidity(provider),
+                format.humidity(*humidity),
             ));
+        }
+    }
 
-            let completion_status = tokio::join!(r_temp, r_pressure, r_humidity);
-            slog::slog_debug!(logger, "Publisher completed with {:?}", completion_status);
+    messages
+}
+
+fn log_publish_result<E: Display>(
+    logger: &Logger,
+    topic: &str,
+    payload: &str,
+    result: Result<(), E>,
+) {
+    match result {
+        Ok(()) => {
+            slog::slog_debug!(logger, "Message queued"; "topic" => topic, "payload" => payload);
+        }
+        Err(e) => {
+            slog::slog_error!(logger, "Message not queued"; "topic" => topic, "payload" => payload, "error" => %e);
         }
     }
 }
 
-fn create_publish_request(msg: String, top: &str) -> Request {
+pub fn create_publish_request(msg: String, top: &str, retain: bool) -> Request {
     let payload: Vec<u8> = msg.into_bytes();
-    let publish = Publish::new(top, QoS::AtLeastOnce, payload);
+    let mut publish = Publish::new(top, QoS::AtLeastOnce, payload);
+    publish.set_retain(retain);
     Request::Publish(publish)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::publisher::{DailyTopics, ProviderTopics};
+    use crate::domain::condition::{Condition, ConditionCategory};
+    use crate::domain::derived::DerivedMetric;
+    use crate::domain::pressure::{PressureMode, PressureSettings};
+    use slog::KV;
+    use std::sync::Mutex;
+
+    type Records = Arc<Mutex<Vec<(String, Vec<(String, String)>)>>>;
+
+    /// Keeps the message and the key-value pairs of every record
+    #[derive(Default)]
+    struct CapturingDrain(Records);
+
+    impl slog::Drain for CapturingDrain {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> Result<(), Self::Err> {
+            let mut pairs = KeyValues::default();
+            record.kv().serialize(record, &mut pairs).unwrap();
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.msg().to_string(), pairs.0));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct KeyValues(Vec<(String, String)>);
+
+    impl slog::Serializer for KeyValues {
+        fn emit_arguments(&mut self, key: slog::Key, val: &std::fmt::Arguments) -> slog::Result {
+            self.0.push((key.to_string(), val.to_string()));
+            Ok(())
+        }
+    }
+
+    fn format() -> PayloadFormat {
+        PayloadFormat::default()
+    }
+
+    fn topics() -> WeatherTopics {
+        WeatherTopics {
+            temperature: "temperature".to_string(),
+            pressure: "pressure".to_string(),
+            humidity: "humidity".to_string(),
+            provider: "provider".to_string(),
+            wind_speed: "wind-speed".to_string(),
+            precipitation: "precipitation".to_string(),
+            pressure_change: "pressure-change".to_string(),
+            pressure_tendency: "pressure-tendency".to_string(),
+            pressure_tendency_code: "pressure-tendency-code".to_string(),
+            sunrise: "sunrise".to_string(),
+            sunset: "sunset".to_string(),
+            day_length: "day-length".to_string(),
+            is_daylight: "is-daylight".to_string(),
+            condition_id: "condition-id".to_string(),
+            condition: "condition".to_string(),
+            condition_description: "condition-description".to_string(),
+            stale: "stale".to_string(),
+            info: "info".to_string(),
+            uptime: "uptime".to_string(),
+            provider_values: None,
+            derived: Vec::new(),
+            daily: DailyTopics::new(&None, "weather"),
+        }
+    }
+
+    #[test]
+    fn messages_formatted() {
+        let weather = CurrentWeather::new(283.3, 1001.0, 55.1);
+        let messages = weather_messages(&weather, &topics(), &format());
+
+        assert_eq!(
+            vec![
+                ("temperature".to_string(), "10.15".to_string()),
+                ("pressure".to_string(), "100100.00".to_string()),
+                ("humidity".to_string(), "55.1".to_string()),
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn provider_published_when_known() {
+        let mut weather = CurrentWeather::new(283.3, 1001.0, 55.1);
+        weather.set_source("open-meteo");
+        let messages = weather_messages(&weather, &topics(), &format());
+
+        assert_eq!(
+            Some(&("provider".to_string(), "open-meteo".to_string())),
+            messages.last()
+        );
+    }
+
+    #[test]
+    fn node_info_and_uptime_published() {
+        let weather =
+            CurrentWeather::new(283.3, 1001.0, 55.1).with_location_name(Some("Prague".to_string()));
+        let (topic, payload) = info_message(&weather, &topics());
+        let info: serde_json::Value = serde_json::from_str(&payload).unwrap();
+
+        assert_eq!("info", topic);
+        assert_eq!(env!("CARGO_PKG_VERSION"), info["version"]);
+        assert_eq!("Prague", info["location"]);
+
+        let started = Instant::now();
+        assert_eq!(
+            ("uptime".to_string(), "90".to_string()),
+            uptime_message(started, started + Duration::from_secs(90), &topics())
+        );
+    }
+
+    #[test]
+    fn unknown_station_pressure_skipped() {
+        let weather = CurrentWeather::new(283.3, 1001.0, 55.1);
+        let mut format = format();
+        format.pressure = PressureSettings {
+            mode: PressureMode::Station,
+            altitude: None,
+        };
+
+        let messages = weather_messages(&weather, &topics(), &format);
+        assert!(messages.iter().all(|(topic, _)| topic != "pressure"));
+    }
+
+    #[test]
+    fn trend_published_once_window_covered() {
+        let mut history = PublisherHistory {
+            state: PublisherState::default(),
+            state_file: None,
+            trend_window: TrendWindow::new(Duration::from_secs(3 * 3600), Duration::from_secs(600)),
+            timezone: None,
+            utc_offset: None,
+            daylight: Daylight::default(),
+            freshness: Freshness::new(Duration::from_secs(3600), SystemTime::UNIX_EPOCH),
+            quota: None,
+            started: Instant::now(),
+        };
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let first = CurrentWeather::new(283.3, 1012.0, 55.1).with_observed_at(start);
+        history.record(&first, &format(), start);
+        assert!(trend_messages(&history, &topics(), &format()).is_empty());
+
+        let second = CurrentWeather::new(283.3, 1010.0, 55.1)
+            .with_observed_at(start + Duration::from_secs(3 * 3600));
+        history.record(&second, &format(), start);
+        assert_eq!(
+            vec![
+                ("pressure-change".to_string(), "-200.00".to_string()),
+                ("pressure-tendency".to_string(), "falling".to_string()),
+            ],
+            trend_messages(&history, &topics(), &format())
+        );
+    }
+
+    #[test]
+    fn daily_statistics_published() {
+        let mut daily = DailyStatistics::default();
+        let day = chrono::NaiveDate::from_ymd(2024, 6, 30);
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        daily.record(at(1), day, &CurrentWeather::new(283.15, 1001.0, 50.0), None);
+        daily.record(at(2), day, &CurrentWeather::new(293.15, 1001.0, 60.0), None);
+
+        let messages = daily_messages(&daily, &topics(), &format());
+
+        assert!(messages.contains(&(
+            "node/weather/weather/-/temperature-daily-max".to_string(),
+            "20.00".to_string()
+        )));
+        assert!(messages.contains(&(
+            "node/weather/weather/-/relative-humidity-daily-mean".to_string(),
+            "55.0".to_string()
+        )));
+        assert_eq!(
+            Some(&(
+                "node/weather/weather/-/daily-date".to_string(),
+                "2024-06-30".to_string()
+            )),
+            messages.first()
+        );
+        assert_eq!(7, messages.len());
+    }
+
+    #[test]
+    fn sun_published_for_coordinates() {
+        let mut daylight = Daylight::default();
+        assert!(sun_messages(&daylight, SystemTime::now(), &topics()).is_empty());
+
+        daylight.update(&CurrentWeather::new(283.3, 1001.0, 55.1).with_coordinates(50.08, 14.42));
+        // 2024-06-21 12:00 CEST
+        let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(1_718_964_000);
+
+        let messages = sun_messages(&daylight, noon, &topics());
+        assert_eq!(3, messages.len());
+        assert_eq!(
+            Some(("is-daylight".to_string(), "true".to_string())),
+            daylight_message(&daylight, noon, &topics())
+        );
+    }
+
+    #[tokio::test]
+    async fn log_sink_drains_requests() {
+        let (mut tx, rx) = tokio::sync::mpsc::channel(2);
+        tx.send(create_publish_request(
+            "10.15".to_string(),
+            "temperature",
+            false,
+        ))
+        .await
+        .unwrap();
+        drop(tx);
+
+        let drain = CapturingDrain::default();
+        let records = drain.0.clone();
+        let logger = Arc::new(Logger::root(drain, slog::o!()));
+        assert!(run_log_sink(rx, logger).await.is_ok());
+
+        let mut pairs = vec![
+            ("topic".to_string(), "temperature".to_string()),
+            ("payload".to_string(), "10.15".to_string()),
+            ("qos".to_string(), "AtLeastOnce".to_string()),
+            ("retain".to_string(), "false".to_string()),
+        ];
+        // Key-value pairs are serialized from the last one
+        pairs.reverse();
+        assert_eq!(
+            vec![("Dry run publish".to_string(), pairs)],
+            *records.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn condition_published() {
+        let weather = CurrentWeather::new(283.3, 1001.0, 55.1).with_condition(Some(Condition {
+            id: Some(500),
+            category: ConditionCategory::Rain,
+            description: Some("slabý déšť".to_string()),
+        }));
+
+        let messages = weather_messages(&weather, &topics(), &format());
+
+        assert!(messages.contains(&("condition-id".to_string(), "500".to_string())));
+        assert!(messages.contains(&("condition".to_string(), "rain".to_string())));
+        assert!(messages.contains(&(
+            "condition-description".to_string(),
+            "slabý déšť".to_string()
+        )));
+    }
+
+    #[test]
+    fn derived_metrics_published() {
+        let mut topics = topics();
+        topics.derived = vec![
+            (DerivedMetric::DewPoint, "dew-point".to_string()),
+            (DerivedMetric::WindChill, "wind-chill".to_string()),
+        ];
+        let weather = CurrentWeather::new(293.15, 1001.0, 50.0);
+
+        let messages = weather_messages(&weather, &topics, &format());
+
+        // Wind chill is skipped as the observation has no wind
+        assert_eq!(
+            Some(&("dew-point".to_string(), "9.26".to_string())),
+            messages.last()
+        );
+    }
+
+    #[test]
+    fn provider_values_published() {
+        let mut topics = topics();
+        topics.provider_values = Some(ProviderTopics::new(&None, "weather"));
+        let mut contribution = CurrentWeather::new(283.3, 1001.0, 55.1);
+        contribution.set_source("met-norway");
+        let weather =
+            CurrentWeather::new(283.3, 1001.0, 55.1).with_contributions(vec![contribution]);
+
+        let messages = weather_messages(&weather, &topics, &format());
+
+        assert!(messages.contains(&(
+            "node/weather/thermometer/met-norway/temperature".to_string(),
+            "10.15".to_string()
+        )));
+        assert!(messages.contains(&(
+            "node/weather/hygrometer/met-norway/relative-humidity".to_string(),
+            "55.1".to_string()
+        )));
+    }
+}
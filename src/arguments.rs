@@ -1,40 +1,182 @@
+use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::num::{NonZeroU16, NonZeroU32};
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::string::ParseError;
+use std::time::Duration;
 
-use crate::app::publisher::PublishingInfo;
+use crate::app::format::{PayloadFormat, PrecipitationUnit, Precision, PressureUnit, SpeedUnit};
+use crate::app::logging::{LogDestination, LogFormat};
+use crate::app::once::PrintFormat;
+use crate::app::publisher::{publishing_problems, PublishingInfo};
+use crate::domain::blend::BlendMethod;
+use crate::domain::cadence::AdaptiveSchedule;
+use crate::domain::derived::DerivedMetric;
+use crate::domain::pressure::{PressureMode, PressureSettings};
+use crate::domain::quota::QuotaBudget;
+use crate::domain::trend::TrendWindow;
+use crate::domain::validation::PlausibilityLimits;
+use crate::weather_client::UnitSystem;
+use chrono_tz::Tz;
+use structopt::clap::{AppSettings, ErrorKind};
 use structopt::StructOpt;
-use uom::si::f32::ThermodynamicTemperature;
-use uom::si::thermodynamic_temperature;
+use uom::si::f32::{Length, Pressure, ThermodynamicTemperature};
+use uom::si::{length, pressure, thermodynamic_temperature};
 use url::Url;
 
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "outdoor",
-    about = "Publishes current weather from OpenWeatherMap to Hardwario/BigClown bus"
+    about = "Publishes current weather from OpenWeatherMap, Open-Meteo or MET Norway to Hardwario/BigClown bus",
+    setting = AppSettings::TrailingVarArg,
+    setting = AppSettings::AllowLeadingHyphen
 )]
-pub struct Args {
-    #[structopt(short = "v", long, parse(from_occurrences))]
+pub struct Cli {
+    #[structopt(short = "v", long, parse(from_occurrences), global = true)]
     pub verbose: u8,
 
-    /// API key from openweathermap.com
-    #[structopt(env)]
-    pub api_key: ApiKey,
+    #[structopt(flatten)]
+    pub logging: LoggingArgs,
+
+    /// Arguments of the run command given without the command
+    #[structopt(hidden = true, allow_hyphen_values = true)]
+    pub run_args: Vec<String>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Parses the command line, the run command is the default one
+    pub fn from_args_or_run() -> Self {
+        Self::from_iter_or_run(std::env::args_os())
+    }
+
+    /// Parses the arguments, those without a command are the arguments of the run command
+    ///
+    /// The daemon used to be started without a command, the service still starts it so.
+    pub fn from_iter_or_run<I>(args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut cli = Cli::from_iter(&args);
+        if cli.command.is_none() {
+            // The trailing run arguments follow the global options, if any
+            let start = args.len().saturating_sub(cli.run_args.len()).max(1);
+            args.insert(start.min(args.len()), "run".into());
+            cli = Cli::from_iter(&args);
+        }
+
+        cli.command = match cli.command {
+            Some(Command::Run(settings)) => Some(Command::Run(Box::new(
+                settings.resolve_positionals().unwrap_or_else(|e| e.exit()),
+            ))),
+            Some(Command::Once(mut once)) => {
+                once.settings = once
+                    .settings
+                    .resolve_positionals()
+                    .unwrap_or_else(|e| e.exit());
+                Some(Command::Once(once))
+            }
+            other => other,
+        };
+        cli
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Fetches the weather periodically and publishes it
+    ///
+    /// The default command, `outdoor <device-name> <mqtt-host>` runs it too.
+    Run(Box<Args>),
+
+    /// Fetches the weather once and prints it with the messages it would publish
+    ///
+    /// Nothing is sent to the MQTT broker unless --publish is given.
+    Once(Box<OnceArgs>),
+
+    /// Looks up OpenWeatherMap cities for the configuration
+    City(CityCommand),
+
+    /// Validates the configuration
+    Config(ConfigCommand),
+}
+
+/// Settings of the daemon, also used by the commands trying them out
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(flatten)]
+    pub provider: ProviderArgs,
 
-    /// Aborts the application if OpenWeatherMap request fails
+    /// Aborts the application if the weather API request fails
     #[structopt(long)]
     pub abort_on_api_error: bool,
 
-    /// OpenWeatherMap city ID
-    ///
-    /// Use a city ID as recomended in https://openweathermap.org/appid
-    /// All city ids should be at http://bulk.openweathermap.org/sample/city.list.json.gz
-    #[structopt(env)]
-    pub city_id: u32,
+    /// Logs the messages instead of publishing them, the broker is not connected
+    #[structopt(long)]
+    pub dry_run: bool,
 
-    #[structopt(short, long, env, default_value = & Units::Celsius.value().unwrap(), possible_values = & Units::variants())]
+    /// Temperature units: celsius, fahrenheit or kelvin
+    ///
+    /// Case insensitive, also accepts C, F, K and the unit systems metric, imperial and standard.
+    #[structopt(short, long, env, default_value = Units::Celsius.value())]
     pub units: Units,
 
+    /// Publishes pressure reduced to sea level or pressure at the site altitude
+    #[structopt(long, env, default_value = "sea-level", possible_values = & PressureMode::variants())]
+    pub pressure_mode: PressureMode,
+
+    /// Site altitude in metres above sea level
+    ///
+    /// Station pressure is computed from it when the provider reports none.
+    #[structopt(long, env, allow_hyphen_values(true))]
+    pub altitude: Option<f32>,
+
+    #[structopt(flatten)]
+    pub output: OutputArgs,
+
+    /// Period the pressure change and tendency are computed over in seconds
+    #[structopt(long, env, default_value = "10800")]
+    pub pressure_trend_window_secs: NonZeroU32,
+
+    /// Time zone of the local day for daily statistics, e.g. Europe/Prague
+    ///
+    /// The UTC offset reported by the provider is used when unset.
+    #[structopt(long, env)]
+    pub timezone: Option<Tz>,
+
+    /// File the pressure history and daily statistics are kept in across restarts
+    #[structopt(long, env, parse(from_os_str))]
+    pub state_file: Option<PathBuf>,
+
+    /// Directory OpenWeatherMap responses are cached in, also across restarts
+    ///
+    /// Requests are keyed by their URL without the API key and revalidated with ETag and
+    /// Last-Modified. Daemons of the same location may share the directory.
+    #[structopt(long, env, parse(from_os_str))]
+    pub http_cache_dir: Option<PathBuf>,
+
+    /// Longest time in seconds a cached response is used without asking the provider
+    ///
+    /// A shorter Cache-Control max-age of the response wins, 0 revalidates every request.
+    #[structopt(long, env, default_value = "540")]
+    pub http_cache_max_age_secs: u32,
+
+    /// Observations colder than this in °C are rejected as implausible
+    #[structopt(long, env, default_value = "-90", allow_hyphen_values(true))]
+    pub min_temperature_celsius: f32,
+
+    /// Observations warmer than this in °C are rejected as implausible
+    #[structopt(long, env, default_value = "60", allow_hyphen_values(true))]
+    pub max_temperature_celsius: f32,
+
+    /// Largest sea-level pressure change in hPa per scraping period, 0 disables the check
+    #[structopt(long, env, default_value = "5")]
+    pub max_pressure_step_hpa: f32,
+
     #[structopt(flatten)]
     pub publishing: MqttPublishingArgs,
 
@@ -44,12 +186,576 @@ pub struct Args {
     #[structopt(short, long, env, default_value = "600")]
     pub interval_secs: NonZeroU32,
 
-    /// Base API for weather requests
+    /// Fetches shortly after the provider is expected to update instead of every period
+    ///
+    /// The update cadence is estimated from the observation times, the scraping period
+    /// remains the longest wait between fetches.
     #[structopt(long)]
-    pub api_base: Option<Url>,
+    pub adaptive_schedule: bool,
+
+    /// Seconds to wait after the expected provider update with the adaptive schedule
+    #[structopt(long, env, default_value = "60")]
+    pub adaptive_delay_secs: u32,
+
+    /// Shortest wait in seconds between fetches with the adaptive schedule
+    #[structopt(long, env, default_value = "120")]
+    pub min_interval_secs: NonZeroU32,
+
+    /// OpenWeatherMap calls allowed per UTC day, the interval stretches to stay within
+    ///
+    /// Counts are kept per API key in the state file, set a share of the quota when several
+    /// daemons use the same key.
+    #[structopt(long, env)]
+    pub daily_call_budget: Option<NonZeroU32>,
+
+    /// OpenWeatherMap calls allowed per UTC month, the interval stretches to stay within
+    #[structopt(long, env)]
+    pub monthly_call_budget: Option<NonZeroU32>,
+
+    /// Listening address of the HTTP health and status server, e.g. 0.0.0.0:8080
+    ///
+    /// Serves /healthz, /readyz and /status. The server is disabled unless set.
+    #[structopt(long, env)]
+    pub http_listen: Option<SocketAddr>,
+
+    /// Number of scraping periods an observation stays fresh for /readyz
+    #[structopt(long, env, default_value = "3")]
+    pub ready_intervals: NonZeroU32,
+
+    /// Age of the latest observation in seconds after which published values are flagged stale
+    #[structopt(long, env, default_value = "3600")]
+    pub stale_after_secs: NonZeroU32,
 
     #[structopt(flatten)]
     pub mqtt_connection: MqttConnectionArgs,
+
+    /// Device name and MQTT host following the API key and city ID given as positionals
+    #[structopt(hidden = true)]
+    pub legacy_positionals: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCommand {
+    /// Checks the settings, the weather API and the MQTT broker and reports every problem
+    ///
+    /// Takes the arguments of the run command, missing required values are reported as
+    /// problems too.
+    Check(CheckArgs),
+}
+
+/// Stands in for required values missing from a checked configuration
+const MISSING: &str = "<missing>";
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::TrailingVarArg, setting = AppSettings::AllowLeadingHyphen)]
+pub struct CheckArgs {
+    /// Arguments of the run command
+    #[structopt(allow_hyphen_values = true)]
+    pub args: Vec<String>,
+}
+
+impl CheckArgs {
+    /// The daemon settings, if any can be parsed, and the problems of the arguments
+    ///
+    /// Arguments are added one by one and those clap rejects are reported and left out,
+    /// so that the rest can be checked. Placeholders fill the missing positional values.
+    pub fn settings(&self) -> (Option<Args>, Vec<String>) {
+        let mut problems = Vec::new();
+        if let Err(e) = Self::parse(&[]) {
+            // A bad environment variable breaks every parse
+            problems.push(Self::describe(&e));
+            return (None, problems);
+        }
+
+        let mut accepted: Vec<&str> = Vec::new();
+        let mut rest = self.args.iter().map(String::as_str).peekable();
+        while let Some(arg) = rest.next() {
+            let mut candidate = accepted.clone();
+            candidate.push(arg);
+            let mut result = Self::parse(&candidate);
+            if let (Err(e), Some(value)) = (&result, rest.peek()) {
+                if e.kind == ErrorKind::EmptyValue {
+                    candidate.push(value);
+                    rest.next();
+                    result = Self::parse(&candidate);
+                }
+            }
+
+            match result {
+                Ok(_) => accepted = candidate,
+                Err(e) => problems.push(Self::describe(&e)),
+            }
+        }
+
+        let settings = match Self::parse(&accepted).and_then(Args::resolve_positionals) {
+            Ok(settings) => settings,
+            Err(e) => {
+                problems.push(Self::describe(&e));
+                return (None, problems);
+            }
+        };
+
+        let missing = [
+            (
+                "<device-name>",
+                "DEVICE_NAME",
+                &settings.publishing.device_name,
+            ),
+            (
+                "<mqtt-host>",
+                "MQTT_HOST",
+                &settings.mqtt_connection.mqtt_host,
+            ),
+        ];
+        problems.extend(
+            missing
+                .iter()
+                .filter(|(_, _, value)| value.as_str() == MISSING)
+                .map(|(name, variable, _)| {
+                    format!("Missing {}, pass it or set {}", name, variable)
+                }),
+        );
+
+        (Some(settings), problems)
+    }
+
+    /// Parses the arguments, with placeholders for missing positional values if need be
+    // repeat_n is too recent for the supported Rust versions
+    #[allow(clippy::manual_repeat_n)]
+    fn parse(args: &[&str]) -> Result<Args, structopt::clap::Error> {
+        let parse = |placeholders: usize| {
+            let separator = if placeholders > 0 { Some("--") } else { None };
+            let args = std::iter::once("outdoor")
+                .chain(args.iter().copied())
+                .chain(separator)
+                .chain(std::iter::repeat(MISSING).take(placeholders));
+            Args::clap()
+                .setting(AppSettings::ColorNever)
+                .get_matches_from_safe(args)
+                .map(|matches| Args::from_clap(&matches))
+        };
+
+        let mut result = parse(0);
+        for placeholders in 1..=2 {
+            match &result {
+                Err(e) if e.kind == ErrorKind::MissingRequiredArgument => {
+                    result = parse(placeholders)
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+
+    fn describe(error: &structopt::clap::Error) -> String {
+        let line = error.message.lines().next().unwrap_or_default();
+        line.trim_start_matches("error:").trim().to_string()
+    }
+
+    pub fn is_missing(value: &str) -> bool {
+        value == MISSING
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CityCommand {
+    /// Finds cities by name and prints their IDs, coordinates and country
+    ///
+    /// Queries the OpenWeatherMap geocoding API with the configured API key, or searches
+    /// the city list offline. Only the city list contains city IDs.
+    Search(CitySearchArgs),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CitySearchArgs {
+    /// City name or its beginning, e.g. Prague
+    pub name: String,
+
+    /// API key from openweathermap.com, the geocoding API requires it
+    #[structopt(long, env)]
+    pub api_key: Option<ApiKey>,
+
+    /// ISO 3166 country code, e.g. CZ
+    #[structopt(long)]
+    pub country: Option<String>,
+
+    /// Downloaded http://bulk.openweathermap.org/sample/city.list.json.gz searched offline
+    #[structopt(long, parse(from_os_str))]
+    pub city_list: Option<PathBuf>,
+
+    /// Maximal number of printed cities
+    #[structopt(long, default_value = "10")]
+    pub limit: NonZeroU32,
+
+    /// Format of the printed cities
+    #[structopt(long, default_value = "table", possible_values = & PrintFormat::variants())]
+    pub format: PrintFormat,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct OnceArgs {
+    #[structopt(flatten)]
+    pub settings: Args,
+
+    /// Publishes the messages to the MQTT broker as well
+    #[structopt(long)]
+    pub publish: bool,
+
+    /// Format of the printed observation and messages
+    #[structopt(long, default_value = "table", possible_values = & PrintFormat::variants())]
+    pub format: PrintFormat,
+}
+
+impl Args {
+    /// Takes the API key and city ID from the former `<api-key> <city-id> <device-name> <mqtt-host>`
+    ///
+    /// Clap assigns the first two of four positionals to the device name and MQTT host,
+    /// they are shifted to the API key and city ID here.
+    pub fn resolve_positionals(mut self) -> Result<Self, structopt::clap::Error> {
+        if self.legacy_positionals.is_empty() {
+            return Ok(self);
+        }
+        if self.legacy_positionals.len() != 2 {
+            return Err(structopt::clap::Error::with_description(
+                "Expected <device-name> <mqtt-host>, optionally preceded by <api-key> <city-id>",
+                ErrorKind::WrongNumberOfValues,
+            ));
+        }
+
+        let mqtt_host = self.legacy_positionals.pop().unwrap_or_default();
+        let device_name = self.legacy_positionals.pop().unwrap_or_default();
+        let api_key = std::mem::replace(&mut self.publishing.device_name, device_name);
+        let city_id = std::mem::replace(&mut self.mqtt_connection.mqtt_host, mqtt_host);
+
+        self.provider.api_key = Some(ApiKey { value: api_key });
+        self.provider.city_id = Some(city_id.parse().map_err(|_| {
+            structopt::clap::Error::with_description(
+                &format!("Invalid city ID \"{}\", expected a number", city_id),
+                ErrorKind::ValueValidation,
+            )
+        })?);
+        Ok(self)
+    }
+}
+
+impl Args {
+    pub fn pressure_settings(&self) -> PressureSettings {
+        PressureSettings {
+            mode: self.pressure_mode,
+            altitude: self.altitude.map(Length::new::<length::meter>),
+        }
+    }
+
+    /// The reference observation may be a fetch interval older than the window
+    pub fn pressure_trend_window(&self) -> TrendWindow {
+        TrendWindow::new(
+            Duration::from_secs(self.pressure_trend_window_secs.get().into()),
+            Duration::from_secs(self.interval_secs.get().into()),
+        )
+    }
+
+    /// Problems found in the settings without contacting any service
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = publishing_problems(&self.publishing);
+
+        if self.min_temperature_celsius >= self.max_temperature_celsius {
+            problems.push(format!(
+                "Minimal temperature {} °C is not below the maximal {} °C",
+                self.min_temperature_celsius, self.max_temperature_celsius
+            ));
+        }
+        if let Err(e) = self.provider.weights() {
+            problems.push(format!("{:#}", e));
+        }
+
+        problems
+    }
+
+    pub fn adaptive_schedule(&self) -> Option<AdaptiveSchedule> {
+        if !self.adaptive_schedule {
+            return None;
+        }
+
+        Some(AdaptiveSchedule::new(
+            Duration::from_secs(self.adaptive_delay_secs.into()),
+            Duration::from_secs(self.min_interval_secs.get().into()),
+        ))
+    }
+
+    pub fn quota_budget(&self) -> QuotaBudget {
+        QuotaBudget {
+            daily: self.daily_call_budget.map(NonZeroU32::get),
+            monthly: self.monthly_call_budget.map(NonZeroU32::get),
+        }
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after_secs.get().into())
+    }
+
+    pub fn plausibility_limits(&self) -> PlausibilityLimits {
+        let step = self.max_pressure_step_hpa;
+
+        PlausibilityLimits {
+            min_temperature: ThermodynamicTemperature::new::<
+                thermodynamic_temperature::degree_celsius,
+            >(self.min_temperature_celsius),
+            max_temperature: ThermodynamicTemperature::new::<
+                thermodynamic_temperature::degree_celsius,
+            >(self.max_temperature_celsius),
+            max_pressure_step: if step > 0.0 {
+                Some(Pressure::new::<pressure::hectopascal>(step))
+            } else {
+                None
+            },
+            interval: Duration::from_secs(self.interval_secs.get().into()),
+        }
+    }
+
+    pub fn payload_format(&self) -> PayloadFormat {
+        PayloadFormat {
+            units: self.units,
+            pressure: self.pressure_settings(),
+            pressure_unit: self.output.pressure_unit,
+            speed_unit: self.output.speed_unit,
+            precipitation_unit: self.output.precipitation_unit,
+            precision: Precision {
+                temperature: self.output.temperature_precision,
+                pressure: self.output.pressure_precision,
+                humidity: self.output.humidity_precision,
+                wind_speed: self.output.wind_speed_precision,
+                precipitation: self.output.precipitation_precision,
+            },
+        }
+    }
+}
+
+// Units and number of decimal places of published values
+#[derive(Debug, StructOpt)]
+pub struct OutputArgs {
+    /// Pressure unit, Hardwario devices publish pascals
+    #[structopt(long, env, default_value = "pa", possible_values = & PressureUnit::variants())]
+    pub pressure_unit: PressureUnit,
+
+    /// Wind speed unit, Beaufort force is published as an integer
+    #[structopt(long, env, default_value = "m/s", possible_values = & SpeedUnit::variants())]
+    pub speed_unit: SpeedUnit,
+
+    #[structopt(long, env, default_value = "mm", possible_values = & PrecipitationUnit::variants())]
+    pub precipitation_unit: PrecipitationUnit,
+
+    #[structopt(long, env, default_value = "2")]
+    pub temperature_precision: usize,
+
+    #[structopt(long, env, default_value = "2")]
+    pub pressure_precision: usize,
+
+    #[structopt(long, env, default_value = "1")]
+    pub humidity_precision: usize,
+
+    #[structopt(long, env, default_value = "1")]
+    pub wind_speed_precision: usize,
+
+    #[structopt(long, env, default_value = "1")]
+    pub precipitation_precision: usize,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ProviderArgs {
+    /// Sources of weather information in order of preference
+    ///
+    /// Further providers are asked only when the preceding ones fail or return stale data,
+    /// e.g. "openweathermap,open-meteo".
+    #[structopt(
+        long = "provider",
+        env = "PROVIDER",
+        default_value = "openweathermap",
+        possible_values = & Provider::variants(),
+        use_delimiter = true
+    )]
+    pub providers: Vec<Provider>,
+
+    /// Whether providers back each other up or their observations are combined
+    #[structopt(long, env, default_value = "failover", possible_values = & ProviderMode::variants())]
+    pub provider_mode: ProviderMode,
+
+    /// How the blend mode combines values of a quantity
+    #[structopt(long, env, default_value = "median", possible_values = & BlendMethod::variants())]
+    pub blend_method: BlendMethod,
+
+    /// Weights of providers for the weighted mean, in the order of providers
+    ///
+    /// All providers weigh the same when omitted.
+    #[structopt(long, env, use_delimiter = true)]
+    pub provider_weights: Vec<f32>,
+
+    /// Blended values further than this many deviations from the median are dropped
+    ///
+    /// The deviation is the median absolute deviation scaled to a standard deviation.
+    /// Outliers can be told apart only with at least three providers.
+    #[structopt(long, env, default_value = "3.0")]
+    pub outlier_threshold: f32,
+
+    /// Observations older than this are stale and not used
+    #[structopt(long, env)]
+    pub max_observation_age_secs: Option<NonZeroU32>,
+
+    /// API key from openweathermap.com
+    #[structopt(long, env)]
+    pub api_key: Option<ApiKey>,
+
+    /// Language of weather condition descriptions, e.g. "cs"
+    ///
+    /// Only OpenWeatherMap provides descriptions. The variable is not LANG to keep
+    /// the system locale from leaking in.
+    #[structopt(long, env = "WEATHER_LANGUAGE")]
+    pub lang: Option<String>,
+
+    /// OpenWeatherMap city ID
+    ///
+    /// Use a city ID as recomended in https://openweathermap.org/appid
+    /// All city ids should be at http://bulk.openweathermap.org/sample/city.list.json.gz,
+    /// `outdoor city search` finds them.
+    #[structopt(long, env)]
+    pub city_id: Option<u32>,
+
+    /// Latitude of the location, used when no city ID is given
+    #[structopt(long, env, allow_hyphen_values(true))]
+    pub latitude: Option<f32>,
+
+    /// Longitude of the location, used when no city ID is given
+    #[structopt(long, env, allow_hyphen_values(true))]
+    pub longitude: Option<f32>,
+
+    /// Base API for weather requests of the primary provider
+    #[structopt(long)]
+    pub api_base: Option<Url>,
+
+    /// User-Agent identifying this installation to MET Norway
+    ///
+    /// MET Norway terms of service require an application name with a contact,
+    /// e.g. "outdoor/0.1 admin@example.com". Anonymous agents may be blocked.
+    #[structopt(long, env)]
+    pub user_agent: Option<String>,
+}
+
+impl ProviderArgs {
+    /// Weights of providers in their order, one unless configured
+    pub fn weights(&self) -> Result<Vec<f32>, anyhow::Error> {
+        if self.provider_weights.is_empty() {
+            return Ok(vec![1.0; self.providers.len()]);
+        }
+
+        if self.provider_weights.len() != self.providers.len() {
+            anyhow::bail!(
+                "{} provider weights given for {} providers",
+                self.provider_weights.len(),
+                self.providers.len()
+            );
+        }
+
+        if self.provider_weights.iter().any(|w| w.is_nan() || *w < 0.0) {
+            anyhow::bail!("Provider weights must not be negative");
+        }
+
+        Ok(self.provider_weights.clone())
+    }
+
+    pub fn max_observation_age(&self) -> Option<Duration> {
+        self.max_observation_age_secs
+            .map(|secs| Duration::from_secs(secs.get().into()))
+    }
+
+    pub fn coordinates(&self) -> Option<(f32, f32)> {
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            _ => None,
+        }
+    }
+
+    /// Human readable location for logs
+    pub fn location_label(&self) -> String {
+        match (self.city_id, self.coordinates()) {
+            (Some(id), _) => id.to_string(),
+            (None, Some((lat, lon))) => format!("{},{}", lat, lon),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderMode {
+    Failover,
+    Blend,
+}
+
+impl ProviderMode {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["failover", "blend"]
+    }
+}
+
+impl FromStr for ProviderMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "failover" => Ok(ProviderMode::Failover),
+            "blend" => Ok(ProviderMode::Blend),
+            _ => anyhow::bail!("Unknown provider mode \"{}\"", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    OpenWeatherMap,
+    OpenMeteo,
+    MetNorway,
+}
+
+impl Provider {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["openweathermap", "open-meteo", "met-norway"]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::OpenWeatherMap => "openweathermap",
+            Provider::OpenMeteo => "open-meteo",
+            Provider::MetNorway => "met-norway",
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "openweathermap" => Ok(Provider::OpenWeatherMap),
+            "open-meteo" => Ok(Provider::OpenMeteo),
+            "met-norway" => Ok(Provider::MetNorway),
+            _ => anyhow::bail!("Unknown weather provider \"{}\"", s),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct LoggingArgs {
+    /// Format of log records
+    #[structopt(long, env, default_value = "text", possible_values = & LogFormat::variants(), global = true)]
+    pub log_format: LogFormat,
+
+    /// Where log records are written
+    ///
+    /// Journald receives structured fields natively regardless of the log format.
+    #[structopt(long, env, default_value = "stderr", possible_values = & LogDestination::variants(), global = true)]
+    pub log_destination: LogDestination,
+
+    /// Log file used with the file log destination
+    #[structopt(long, env, parse(from_os_str), global = true)]
+    pub log_file: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -76,6 +782,15 @@ pub struct MqttConnectionArgs {
     pub mqtt_throttle_ms: u64,
 }
 
+impl MqttConnectionArgs {
+    /// A distinct client id for a short connection, the broker would disconnect the daemon
+    /// connecting with the same id
+    pub fn with_client_suffix(mut self, suffix: &str) -> Self {
+        self.mqtt_id = format!("{}-{}-{}", self.mqtt_id, suffix, std::process::id());
+        self
+    }
+}
+
 #[derive(Debug, StructOpt)]
 pub struct MqttPublishingArgs {
     /// Identification of this agent in published weather information
@@ -96,6 +811,25 @@ pub struct MqttPublishingArgs {
 
     #[structopt(long, env, default_value = "0:0")]
     pub channel_hygrometer: String,
+
+    /// Publishes raw values of each blended provider using the provider name as the channel
+    #[structopt(long)]
+    pub publish_provider_values: bool,
+
+    /// Quantities computed from the observation, e.g. "dew-point,humidex"
+    ///
+    /// Wind chill and apparent temperature require a provider reporting wind speed.
+    #[structopt(
+        long,
+        env,
+        possible_values = & DerivedMetric::variants(),
+        use_delimiter = true
+    )]
+    pub derived_metrics: Vec<DerivedMetric>,
+
+    /// Topic of derived quantities with placeholders {prefix}, {device} and {metric}
+    #[structopt(long, env, default_value = "{prefix}node/{device}/weather/-/{metric}")]
+    pub derived_topic_template: String,
 }
 
 impl PublishingInfo for MqttPublishingArgs {
@@ -118,9 +852,21 @@ impl PublishingInfo for MqttPublishingArgs {
     fn get_channel_hygrometer(&self) -> &str {
         &self.channel_hygrometer
     }
+
+    fn publish_provider_values(&self) -> bool {
+        self.publish_provider_values
+    }
+
+    fn get_derived_metrics(&self) -> &[DerivedMetric] {
+        &self.derived_metrics
+    }
+
+    fn get_derived_topic_template(&self) -> &str {
+        &self.derived_topic_template
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Units {
     Kelvin,
     Fahrenheit,
@@ -136,32 +882,46 @@ impl Units {
         }
     }
 
-    pub fn value(&self) -> Option<&'static str> {
+    pub fn value(&self) -> &'static str {
         match *self {
-            Units::Celsius => Some("celsius"),
-            Units::Fahrenheit => Some("fahrenheit"),
-            Units::Kelvin => None,
+            Units::Celsius => "celsius",
+            Units::Fahrenheit => "fahrenheit",
+            Units::Kelvin => "kelvin",
         }
     }
 
-    fn variants() -> Vec<&'static str> {
+    pub fn variants() -> Vec<&'static str> {
         vec!["celsius", "fahrenheit", "kelvin"]
     }
+
+    /// OpenWeatherMap unit system reporting the temperature in these units
+    pub fn unit_system(self) -> UnitSystem {
+        match self {
+            Units::Celsius => UnitSystem::Metric,
+            Units::Fahrenheit => UnitSystem::Imperial,
+            Units::Kelvin => UnitSystem::Standard,
+        }
+    }
 }
 
 impl FromStr for Units {
-    type Err = ParseError;
-    fn from_str(day: &str) -> Result<Self, Self::Err> {
-        match day {
-            "celsius" => Ok(Units::Celsius),
-            "fahrenheit" => Ok(Units::Fahrenheit),
-            "kelvin" => Ok(Units::Kelvin),
-            _ => unreachable!(),
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "celsius" | "c" | "metric" => Ok(Units::Celsius),
+            "fahrenheit" | "f" | "imperial" => Ok(Units::Fahrenheit),
+            "kelvin" | "k" | "standard" => Ok(Units::Kelvin),
+            _ => anyhow::bail!(
+                "Unknown units \"{}\", use one of {}",
+                s,
+                Units::variants().join(", ")
+            ),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ApiKey {
     value: String,
 }
@@ -215,3 +975,172 @@ impl From<Password> for String {
         p.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn units_aliases() {
+        assert_eq!(Units::Celsius, "C".parse().unwrap());
+        assert_eq!(Units::Celsius, "Metric".parse().unwrap());
+        assert_eq!(Units::Fahrenheit, "imperial".parse().unwrap());
+        assert_eq!(Units::Kelvin, "KELVIN".parse().unwrap());
+        assert!("rankine".parse::<Units>().is_err());
+    }
+
+    #[test]
+    fn positional_api_key_and_city_id() {
+        let settings = Args::from_iter(&["outdoor", "KEY", "3067696", "dev", "localhost"])
+            .resolve_positionals()
+            .unwrap();
+        assert_eq!("KEY", String::from(settings.provider.api_key.unwrap()));
+        assert_eq!(Some(3_067_696), settings.provider.city_id);
+        assert_eq!("dev", settings.publishing.device_name);
+        assert_eq!("localhost", settings.mqtt_connection.mqtt_host);
+
+        let settings = Args::from_iter(&["outdoor", "dev", "localhost", "--city-id", "1"])
+            .resolve_positionals()
+            .unwrap();
+        assert_eq!("dev", settings.publishing.device_name);
+        assert!(settings.provider.api_key.is_none());
+
+        let prague = ["outdoor", "KEY", "Prague", "dev", "localhost"];
+        assert!(Args::from_iter(&prague).resolve_positionals().is_err());
+        let three = ["outdoor", "KEY", "dev", "localhost"];
+        assert!(Args::from_iter(&three).resolve_positionals().is_err());
+    }
+
+    #[test]
+    fn publish_provider_values_is_flag() {
+        let daemon = ["dev", "localhost", "--publish-provider-values"];
+
+        let settings = Args::from_iter(["outdoor"].iter().chain(&daemon));
+        assert!(settings.publishing.publish_provider_values);
+        assert_eq!("localhost", settings.mqtt_connection.mqtt_host);
+    }
+
+    #[test]
+    fn dry_run_is_flag() {
+        let daemon = ["dev", "localhost", "--api-key", "x", "--city-id", "1"];
+
+        let after = Args::from_iter(["outdoor"].iter().chain(&daemon).chain(&["--dry-run"]));
+        assert!(after.dry_run);
+        let before = Args::from_iter(["outdoor", "--dry-run"].iter().chain(&daemon));
+        assert!(before.dry_run);
+        assert_eq!("localhost", before.mqtt_connection.mqtt_host);
+        assert!(!Args::from_iter(["outdoor"].iter().chain(&daemon)).dry_run);
+    }
+
+    #[test]
+    fn adaptive_schedule_is_flag() {
+        let daemon = ["dev", "localhost", "--api-key", "x", "--city-id", "1"];
+
+        let settings = Args::from_iter(["outdoor", "--adaptive-schedule"].iter().chain(&daemon));
+        assert!(settings.adaptive_schedule);
+        assert_eq!("dev", settings.publishing.device_name);
+        assert!(settings.adaptive_schedule().is_some());
+    }
+
+    #[test]
+    fn commands_take_own_arguments() {
+        let search = Cli::from_iter(&["outdoor", "city", "search", "Prague", "--country", "CZ"]);
+        match search.command {
+            Some(Command::City(CityCommand::Search(search))) => {
+                assert_eq!("Prague", search.name);
+                assert_eq!(Some("CZ".to_string()), search.country);
+            }
+            other => panic!("Expected a city search, got {:?}", other),
+        }
+
+        let run = Cli::from_iter_or_run(&[
+            "outdoor",
+            "run",
+            "dev",
+            "localhost",
+            "--api-key",
+            "x",
+            "--city-id",
+            "1",
+            "-vv",
+            "--log-format",
+            "json",
+        ]);
+        assert_eq!(2, run.verbose);
+        assert_eq!(LogFormat::Json, run.logging.log_format);
+        assert!(matches!(run.command, Some(Command::Run(_))));
+    }
+
+    #[test]
+    fn run_is_default_command() {
+        let cli = Cli::from_iter_or_run(&["outdoor", "-v", "KEY", "1", "dev", "localhost"]);
+        assert_eq!(1, cli.verbose);
+        match cli.command {
+            Some(Command::Run(settings)) => {
+                assert_eq!(Some(1), settings.provider.city_id);
+                assert_eq!("dev", settings.publishing.device_name);
+                assert_eq!("localhost", settings.mqtt_connection.mqtt_host);
+            }
+            other => panic!("Expected the run command, got {:?}", other),
+        }
+
+        let cli = Cli::from_iter_or_run(&["outdoor", "--dry-run", "dev", "localhost", "-v"]);
+        assert_eq!(1, cli.verbose);
+        match cli.command {
+            Some(Command::Run(settings)) => assert!(settings.dry_run),
+            other => panic!("Expected the run command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_reports_missing_values() {
+        let check = CheckArgs {
+            args: vec!["--api-key".to_string(), "x".to_string()],
+        };
+        let (settings, problems) = check.settings();
+        let settings = settings.unwrap();
+
+        assert_eq!(2, problems.len(), "{:?}", problems);
+        assert!(problems[1].contains("MQTT_HOST"));
+        assert!(settings.provider.api_key.is_some());
+
+        let check = CheckArgs {
+            args: vec!["dev".to_string()],
+        };
+        let (settings, problems) = check.settings();
+        assert_eq!("dev", settings.unwrap().publishing.device_name);
+        assert_eq!(
+            vec!["Missing <mqtt-host>, pass it or set MQTT_HOST"],
+            problems
+        );
+    }
+
+    #[test]
+    fn check_reports_every_bad_argument() {
+        let args = "--units bogus --bogus --channel-thermometer x --interval-secs=0 dev localhost";
+        let check = CheckArgs {
+            args: args.split(' ').map(String::from).collect(),
+        };
+        let (settings, problems) = check.settings();
+        let settings = settings.unwrap();
+
+        assert_eq!(3, problems.len(), "{:?}", problems);
+        assert!(problems[0].contains("--units"), "{}", problems[0]);
+        assert!(problems[1].contains("--bogus"), "{}", problems[1]);
+        assert!(problems[2].contains("--interval-secs"), "{}", problems[2]);
+        assert_eq!("localhost", settings.mqtt_connection.mqtt_host);
+        assert!(settings
+            .problems()
+            .iter()
+            .any(|p| p.contains("thermometer")));
+    }
+
+    #[test]
+    fn one_off_client_id_distinct() {
+        let connection = MqttConnectionArgs::from_iter(&["outdoor", "localhost"]);
+        assert_eq!("weather", connection.mqtt_id);
+
+        let once = connection.with_client_suffix("once");
+        assert_eq!(format!("weather-once-{}", std::process::id()), once.mqtt_id);
+    }
+}
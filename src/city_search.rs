@@ -0,0 +1,274 @@
+use std::io::{BufReader, Read};
+
+use flate2::read::GzDecoder;
+use slog::Logger;
+use url::Url;
+
+use crate::weather_types::Coordinates;
+
+/// City wanted by the operator
+#[derive(Debug, Clone)]
+pub struct CityQuery {
+    pub name: String,
+    /// ISO 3166 country code, e.g. CZ
+    pub country: Option<String>,
+    pub limit: usize,
+}
+
+impl CityQuery {
+    /// Rank of the match, exact names first, `None` when the city does not match
+    fn rank(&self, name: &str, country: &str) -> Option<u8> {
+        if let Some(wanted) = &self.country {
+            if !wanted.eq_ignore_ascii_case(country) {
+                return None;
+            }
+        }
+
+        let name = name.to_lowercase();
+        let wanted = self.name.to_lowercase();
+        if name == wanted {
+            Some(0)
+        } else if name.starts_with(&wanted) {
+            Some(1)
+        } else {
+            None
+        }
+    }
+}
+
+/// City found either in the city list or by the geocoding API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CityMatch {
+    /// OpenWeatherMap city ID, the geocoding API does not report it
+    pub id: Option<u64>,
+    pub name: String,
+    pub state: Option<String>,
+    pub country: String,
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+/// Entry of http://bulk.openweathermap.org/sample/city.list.json.gz
+#[derive(Deserialize, Debug)]
+struct CityListEntry {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    state: String,
+    country: String,
+    coord: Coordinates,
+}
+
+/// Searches the gzipped OpenWeatherMap city list
+pub fn search_city_list<R: Read>(
+    reader: R,
+    query: &CityQuery,
+) -> Result<Vec<CityMatch>, anyhow::Error> {
+    let decoder = BufReader::new(GzDecoder::new(reader));
+    let entries: Vec<CityListEntry> = serde_json::from_reader(decoder)?;
+
+    let mut found: Vec<(u8, CityMatch)> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            query.rank(&entry.name, &entry.country).map(|rank| {
+                (
+                    rank,
+                    CityMatch {
+                        id: Some(entry.id),
+                        name: entry.name,
+                        state: Some(entry.state).filter(|s| !s.is_empty()),
+                        country: entry.country,
+                        latitude: entry.coord.lat,
+                        longitude: entry.coord.lon,
+                    },
+                )
+            })
+        })
+        .collect();
+    found.sort_by(|(a, x), (b, y)| a.cmp(b).then_with(|| x.name.cmp(&y.name)));
+
+    Ok(found
+        .into_iter()
+        .take(query.limit)
+        .map(|(_, city)| city)
+        .collect())
+}
+
+/// Place returned by the OpenWeatherMap geocoding API
+#[derive(Deserialize, Debug)]
+struct GeocodedPlace {
+    name: String,
+    lat: f32,
+    lon: f32,
+    country: String,
+    state: Option<String>,
+}
+
+pub struct GeocodingClient {
+    base_url: Url,
+    api_key: String,
+    http_client: reqwest::Client,
+    logger: Logger,
+}
+
+impl GeocodingClient {
+    pub async fn search(&self, query: &CityQuery) -> Result<Vec<CityMatch>, anyhow::Error> {
+        let url = self.get_direct_url(query)?;
+        let response = self.http_client.get(url.as_str()).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        slog::slog_debug!(self.logger, "OpenWeatherMap geocoding responded"; "status" => status.as_u16());
+        if !status.is_success() {
+            anyhow::bail!("Geocoding failed with status {}: {}", status, body);
+        }
+
+        parse_places(&body)
+    }
+
+    fn get_direct_url(&self, query: &CityQuery) -> Result<Url, anyhow::Error> {
+        let q = match &query.country {
+            Some(country) => format!("{},{}", query.name, country),
+            None => query.name.clone(),
+        };
+        let base = format!("{}direct", self.base_url);
+
+        Ok(Url::parse_with_params(
+            &base,
+            &[
+                ("q", q),
+                ("limit", query.limit.to_string()),
+                ("appid", self.api_key.clone()),
+            ],
+        )?)
+    }
+}
+
+/// Geocoded places carry no city ID
+fn parse_places(body: &str) -> Result<Vec<CityMatch>, anyhow::Error> {
+    let places: Vec<GeocodedPlace> = serde_json::from_str(body)?;
+
+    Ok(places
+        .into_iter()
+        .map(|place| CityMatch {
+            id: None,
+            name: place.name,
+            state: place.state,
+            country: place.country,
+            latitude: place.lat,
+            longitude: place.lon,
+        })
+        .collect())
+}
+
+pub struct GeocodingClientBuilder {
+    api_key: String,
+    base_url: Url,
+    logger: Option<Logger>,
+}
+
+impl GeocodingClientBuilder {
+    pub fn new<T: Into<String>>(api_key: T) -> Self {
+        let default_base_url = "https://api.openweathermap.org/geo/1.0/";
+        let base_url: Url = Url::parse(default_base_url)
+            .unwrap_or_else(|_| panic!("Broken default hardcoded base URL {}", &default_base_url));
+
+        GeocodingClientBuilder {
+            api_key: api_key.into(),
+            base_url,
+            logger: None,
+        }
+    }
+
+    pub fn with_logger(&mut self, logger: Logger) {
+        self.logger = Some(logger);
+    }
+
+    pub fn build(self) -> Result<GeocodingClient, anyhow::Error> {
+        Ok(GeocodingClient {
+            base_url: self.base_url,
+            api_key: self.api_key,
+            http_client: reqwest::ClientBuilder::new().build()?,
+            logger: self
+                .logger
+                .unwrap_or_else(|| Logger::root(slog::Discard, slog::o!())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const CITY_LIST: &str = r#"[
+        {"id": 3067696, "name": "Prague", "state": "", "country": "CZ", "coord": {"lon": 14.42076, "lat": 50.088039}},
+        {"id": 4548393, "name": "Prague", "state": "OK", "country": "US", "coord": {"lon": -96.685028, "lat": 35.486992}},
+        {"id": 3067695, "name": "Praha", "state": "", "country": "CZ", "coord": {"lon": 14.41667, "lat": 50.083328}},
+        {"id": 2950159, "name": "Berlin", "state": "", "country": "DE", "coord": {"lon": 13.41053, "lat": 52.524368}}
+    ]"#;
+
+    fn gzipped(content: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn query(name: &str, country: Option<&str>) -> CityQuery {
+        CityQuery {
+            name: name.to_string(),
+            country: country.map(String::from),
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn city_list_filtered_by_country() {
+        let found =
+            search_city_list(gzipped(CITY_LIST).as_slice(), &query("prague", Some("cz"))).unwrap();
+
+        assert_eq!(1, found.len());
+        assert_eq!(Some(3067696), found[0].id);
+        assert_eq!(None, found[0].state);
+    }
+
+    #[test]
+    fn exact_names_before_prefixes() {
+        let found = search_city_list(gzipped(CITY_LIST).as_slice(), &query("Pra", None)).unwrap();
+        let names: Vec<&str> = found.iter().map(|c| c.name.as_str()).collect();
+
+        assert_eq!(vec!["Prague", "Prague", "Praha"], names);
+
+        let found = search_city_list(gzipped(CITY_LIST).as_slice(), &query("Praha", None)).unwrap();
+        assert_eq!(Some(3067695), found[0].id);
+    }
+
+    #[test]
+    fn geocoding_url_contains_country() {
+        let client = GeocodingClientBuilder::new("key").build().unwrap();
+        let url = client.get_direct_url(&query("Prague", Some("CZ"))).unwrap();
+
+        assert_eq!(
+            "https://api.openweathermap.org/geo/1.0/direct?q=Prague%2CCZ&limit=10&appid=key",
+            url.as_str()
+        );
+    }
+
+    #[test]
+    fn geocoded_places_parsed() {
+        let body = r#"[
+            {"name": "Prague", "local_names": {"cs": "Praha"}, "lat": 50.0874654, "lon": 14.4212535, "country": "CZ"},
+            {"name": "Prague", "lat": 35.4867, "lon": -96.685, "country": "US", "state": "Oklahoma"}
+        ]"#;
+
+        let found = parse_places(body).unwrap();
+
+        assert_eq!(2, found.len());
+        assert_eq!(None, found[0].id);
+        assert_eq!(None, found[0].state);
+        assert_eq!(Some("Oklahoma".to_string()), found[1].state);
+        assert_eq!(-96.685, found[1].longitude);
+    }
+}
@@ -0,0 +1,343 @@
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use slog::Logger;
+use uom::si::f32::{Length, Pressure, ThermodynamicTemperature, Velocity};
+use uom::si::{length, pressure, thermodynamic_temperature, velocity};
+
+use crate::domain::current_weather::{CurrentWeather, Humidity};
+use crate::domain::failover::NamedClient;
+use crate::domain::interfaces::WeatherClient;
+
+/// Scales the median absolute deviation to the standard deviation of a normal distribution
+const MAD_SCALE: f32 = 1.4826;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMethod {
+    Median,
+    WeightedMean,
+}
+
+impl BlendMethod {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["median", "weighted-mean"]
+    }
+}
+
+impl FromStr for BlendMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "median" => Ok(BlendMethod::Median),
+            "weighted-mean" => Ok(BlendMethod::WeightedMean),
+            _ => anyhow::bail!("Unknown blend method \"{}\"", s),
+        }
+    }
+}
+
+/// How values are combined and which of them are rejected as outliers
+#[derive(Debug, Clone, Copy)]
+pub struct BlendSettings {
+    pub method: BlendMethod,
+    /// Values further than this many scaled MADs from the median are dropped
+    pub outlier_threshold: f32,
+    pub max_age: Option<Duration>,
+}
+
+pub struct WeightedClient {
+    pub client: NamedClient,
+    pub weight: f32,
+}
+
+/// Asks all providers at once and combines their observations quantity by quantity
+pub struct BlendClient {
+    providers: Vec<WeightedClient>,
+    settings: BlendSettings,
+    logger: Logger,
+}
+
+impl BlendClient {
+    pub fn new(providers: Vec<WeightedClient>, settings: BlendSettings, logger: Logger) -> Self {
+        BlendClient {
+            providers,
+            settings,
+            logger,
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherClient for BlendClient {
+    async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+        let requests = self
+            .providers
+            .iter()
+            .map(|p| p.client.client.get_current_weather());
+        let results = join_all(requests).await;
+        let now = SystemTime::now();
+
+        let mut observations: Vec<(CurrentWeather, f32)> = Vec::new();
+        for (provider, result) in self.providers.iter().zip(results) {
+            let name = &provider.client.name;
+            match result {
+                Ok(mut weather) => match (weather.age(now), self.settings.max_age) {
+                    (Some(age), Some(max_age)) if age > max_age => {
+                        slog::slog_warn!(self.logger, "Weather provider returned stale data";
+                            "provider" => name, "age_secs" => age.as_secs());
+                    }
+                    _ => {
+                        weather.set_source(name);
+                        observations.push((weather, provider.weight));
+                    }
+                },
+                Err(e) => {
+                    slog::slog_warn!(self.logger, "Weather provider failed";
+                        "provider" => name, "error" => format!("{:#}", e));
+                }
+            }
+        }
+
+        if observations.is_empty() {
+            anyhow::bail!("No weather provider returned a fresh observation");
+        }
+
+        blend(observations, &self.settings)
+    }
+}
+
+fn blend(
+    observations: Vec<(CurrentWeather, f32)>,
+    settings: &BlendSettings,
+) -> Result<CurrentWeather, anyhow::Error> {
+    let collect = |f: &dyn Fn(&CurrentWeather) -> f32| -> Vec<(f32, f32)> {
+        observations
+            .iter()
+            .map(|(w, weight)| (f(w), *weight))
+            .collect()
+    };
+
+    let temperature = aggregate(
+        &collect(&|w| {
+            w.get_temperature()
+                .get::<thermodynamic_temperature::kelvin>()
+        }),
+        settings,
+    );
+    let pressure = aggregate(
+        &collect(&|w| w.get_pressure().get::<pressure::pascal>()),
+        settings,
+    );
+    let humidity = aggregate(&collect(&|w| *w.get_humidity().as_ref()), settings);
+    // Not every provider reports station pressure, wind or precipitation
+    let station_pressures: Vec<(f32, f32)> = observations
+        .iter()
+        .filter_map(|(w, weight)| {
+            w.get_station_pressure()
+                .map(|p| (p.get::<pressure::pascal>(), *weight))
+        })
+        .collect();
+    let station_pressure = aggregate(&station_pressures, settings);
+    let wind_speeds: Vec<(f32, f32)> = observations
+        .iter()
+        .filter_map(|(w, weight)| {
+            w.get_wind_speed()
+                .map(|v| (v.get::<velocity::meter_per_second>(), *weight))
+        })
+        .collect();
+    let wind_speed = aggregate(&wind_speeds, settings);
+    let precipitations: Vec<(f32, f32)> = observations
+        .iter()
+        .filter_map(|(w, weight)| {
+            w.get_precipitation()
+                .map(|p| (p.get::<length::millimeter>(), *weight))
+        })
+        .collect();
+    let precipitation = aggregate(&precipitations, settings);
+
+    let (temperature, pressure, humidity) = match (temperature, pressure, humidity) {
+        (Some(t), Some(p), Some(h)) => (t, p, h),
+        _ => anyhow::bail!("Weather observations could not be blended"),
+    };
+
+    let source = observations
+        .iter()
+        .filter_map(|(w, _)| w.get_source())
+        .collect::<Vec<&str>>()
+        .join("+");
+    // The oldest observation makes the blend stale first
+    let observed_at = observations
+        .iter()
+        .filter_map(|(w, _)| w.get_observed_at())
+        .min();
+
+    let mut blended = CurrentWeather::from_quantities(
+        ThermodynamicTemperature::new::<thermodynamic_temperature::kelvin>(temperature),
+        Pressure::new::<pressure::pascal>(pressure),
+        Humidity::new(humidity.clamp(0.0, 100.0)),
+    );
+    if let Some(observed_at) = observed_at {
+        blended = blended.with_observed_at(observed_at);
+    }
+    if let Some(utc_offset) = observations.iter().find_map(|(w, _)| w.get_utc_offset()) {
+        blended = blended.with_utc_offset(utc_offset);
+    }
+    if let Some((latitude, longitude)) = observations.iter().find_map(|(w, _)| w.get_coordinates())
+    {
+        blended = blended.with_coordinates(latitude, longitude);
+    }
+    if let Some(sun_times) = observations.iter().find_map(|(w, _)| w.get_sun_times()) {
+        blended = blended.with_sun_times(sun_times);
+    }
+    // Conditions cannot be averaged, the most preferred provider describes them
+    blended = blended.with_condition(
+        observations
+            .iter()
+            .find_map(|(w, _)| w.get_condition())
+            .cloned(),
+    );
+    blended = blended.with_location_name(
+        observations
+            .iter()
+            .find_map(|(w, _)| w.get_location_name())
+            .map(String::from),
+    );
+    if let Some(station_pressure) = station_pressure {
+        blended =
+            blended.with_station_pressure(Pressure::new::<pressure::pascal>(station_pressure));
+    }
+    if let Some(precipitation) = precipitation {
+        blended = blended.with_precipitation(Length::new::<length::millimeter>(precipitation));
+    }
+    if let Some(wind_speed) = wind_speed {
+        blended = blended.with_wind_speed(Velocity::new::<velocity::meter_per_second>(wind_speed));
+    }
+    blended.set_source(&source);
+
+    Ok(blended.with_contributions(observations.into_iter().map(|(w, _)| w).collect()))
+}
+
+/// Combines `(value, weight)` pairs after dropping outliers
+pub fn aggregate(values: &[(f32, f32)], settings: &BlendSettings) -> Option<f32> {
+    let accepted = reject_outliers(values, settings.outlier_threshold);
+
+    match settings.method {
+        BlendMethod::Median => median(&accepted.iter().map(|(v, _)| *v).collect::<Vec<f32>>()),
+        BlendMethod::WeightedMean => weighted_mean(&accepted),
+    }
+}
+
+fn reject_outliers(values: &[(f32, f32)], threshold: f32) -> Vec<(f32, f32)> {
+    // Two values cannot tell which one is wrong
+    if values.len() < 3 {
+        return values.to_vec();
+    }
+
+    let plain: Vec<f32> = values.iter().map(|(v, _)| *v).collect();
+    let center = match median(&plain) {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+    let deviations: Vec<f32> = plain.iter().map(|v| (v - center).abs()).collect();
+    let mad = median(&deviations).unwrap_or(0.0) * MAD_SCALE;
+
+    // A zero MAD means most values agree exactly, keep only those
+    values
+        .iter()
+        .filter(|(v, _)| (v - center).abs() <= threshold * mad)
+        .cloned()
+        .collect()
+}
+
+fn median(values: &[f32]) -> Option<f32> {
+    let mut sorted: Vec<f32> = values.iter().cloned().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        Some(sorted[middle])
+    } else {
+        Some((sorted[middle - 1] + sorted[middle]) / 2.0)
+    }
+}
+
+fn weighted_mean(values: &[(f32, f32)]) -> Option<f32> {
+    let total_weight: f32 = values.iter().map(|(_, w)| w).sum();
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    Some(values.iter().map(|(v, w)| v * w).sum::<f32>() / total_weight)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static EPSILON: f32 = 0.0001;
+
+    fn settings(method: BlendMethod) -> BlendSettings {
+        BlendSettings {
+            method,
+            outlier_threshold: 3.0,
+            max_age: None,
+        }
+    }
+
+    #[test]
+    fn median_of_odd_and_even() {
+        assert_eq!(Some(2.0), median(&[3.0, 1.0, 2.0]));
+        assert_eq!(Some(2.5), median(&[4.0, 1.0, 2.0, 3.0]));
+        assert_eq!(None, median(&[]));
+    }
+
+    #[test]
+    fn weighted_mean_respects_weights() {
+        let mean = weighted_mean(&[(10.0, 3.0), (20.0, 1.0)]).unwrap();
+
+        assert!((12.5 - mean).abs() < EPSILON, "{}", mean);
+        assert_eq!(None, weighted_mean(&[(10.0, 0.0)]));
+    }
+
+    #[test]
+    fn outlier_rejected() {
+        let values = [(20.1, 1.0), (20.3, 1.0), (19.9, 1.0), (35.0, 1.0)];
+        let mean = aggregate(&values, &settings(BlendMethod::WeightedMean)).unwrap();
+
+        assert!((20.1 - mean).abs() < EPSILON, "{}", mean);
+    }
+
+    #[test]
+    fn two_values_kept() {
+        let values = [(20.0, 1.0), (35.0, 1.0)];
+        let median = aggregate(&values, &settings(BlendMethod::Median)).unwrap();
+
+        assert!((27.5 - median).abs() < EPSILON, "{}", median);
+    }
+
+    #[test]
+    fn observations_blended() {
+        let observations = vec![
+            (CurrentWeather::new(283.0, 1000.0, 50.0), 1.0),
+            (CurrentWeather::new(284.0, 1002.0, 60.0), 1.0),
+            (CurrentWeather::new(283.5, 1001.0, 55.0), 1.0),
+        ];
+        let blended = blend(observations, &settings(BlendMethod::Median)).unwrap();
+
+        let t = blended
+            .get_temperature()
+            .get::<thermodynamic_temperature::kelvin>();
+        let p = blended.get_pressure().get::<pressure::hectopascal>();
+        let h: &f32 = blended.get_humidity().as_ref();
+
+        assert!((283.5 - t).abs() < 0.001, "{}", t);
+        assert!((1001.0 - p).abs() < 0.001, "{}", p);
+        assert!((55.0 - h).abs() < EPSILON, "{}", h);
+        assert_eq!(3, blended.get_contributions().len());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Number of recent update gaps the cadence is estimated from
+const SAMPLES: usize = 6;
+
+/// Estimates how often the provider refreshes its observations
+#[derive(Debug, Clone, Default)]
+pub struct UpdateCadence {
+    last_update: Option<SystemTime>,
+    gaps: VecDeque<Duration>,
+}
+
+impl UpdateCadence {
+    /// Repeated observation times mean the provider has not updated yet
+    pub fn record(&mut self, observed_at: SystemTime) {
+        match self.last_update {
+            Some(last) if observed_at <= last => {}
+            Some(last) => {
+                self.gaps
+                    .push_back(observed_at.duration_since(last).unwrap_or_default());
+                if self.gaps.len() > SAMPLES {
+                    self.gaps.pop_front();
+                }
+                self.last_update = Some(observed_at);
+            }
+            None => self.last_update = Some(observed_at),
+        }
+    }
+
+    /// The shortest recent gap, polling misses some updates and stretches the others
+    pub fn period(&self) -> Option<Duration> {
+        self.gaps.iter().min().copied()
+    }
+
+    /// Time of the first update expected after `now`
+    pub fn next_update(&self, now: SystemTime) -> Option<SystemTime> {
+        let last = self.last_update?;
+        let period = self.period()?.as_secs().max(1);
+        let elapsed = now.duration_since(last).unwrap_or_default().as_secs();
+
+        Some(last + Duration::from_secs((elapsed / period + 1) * period))
+    }
+}
+
+/// Schedules fetches shortly after the provider is expected to update
+#[derive(Debug, Clone)]
+pub struct AdaptiveSchedule {
+    pub cadence: UpdateCadence,
+    /// Wait after the expected update, providers publish with a lag
+    pub delay: Duration,
+    /// Shortest wait between fetches protecting the API quota
+    pub min_interval: Duration,
+}
+
+impl AdaptiveSchedule {
+    pub fn new(delay: Duration, min_interval: Duration) -> Self {
+        AdaptiveSchedule {
+            cadence: UpdateCadence::default(),
+            delay,
+            min_interval,
+        }
+    }
+
+    /// Wait before the next fetch, `None` until the cadence is known
+    pub fn next_fetch(&self, now: SystemTime, max_interval: Duration) -> Option<Duration> {
+        // An update expected less than the delay ago has not been fetched yet
+        let since = now.checked_sub(self.delay).unwrap_or(now);
+        let next_update = self.cadence.next_update(since)?;
+        let wait = (next_update + self.delay)
+            .duration_since(now)
+            .unwrap_or_default();
+
+        Some(wait.clamp(self.min_interval.min(max_interval), max_interval))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000 + minutes * 60)
+    }
+
+    #[test]
+    fn cadence_from_shortest_gap() {
+        let mut cadence = UpdateCadence::default();
+        cadence.record(at(0));
+        assert_eq!(None, cadence.period());
+
+        cadence.record(at(10));
+        cadence.record(at(10));
+        cadence.record(at(30));
+
+        assert_eq!(Some(10 * MINUTE), cadence.period());
+        assert_eq!(Some(at(40)), cadence.next_update(at(32)));
+        assert_eq!(Some(at(50)), cadence.next_update(at(40)));
+    }
+
+    #[test]
+    fn fetch_follows_expected_update() {
+        let mut schedule = AdaptiveSchedule::new(MINUTE, 2 * MINUTE);
+        assert_eq!(None, schedule.next_fetch(at(0), 15 * MINUTE));
+
+        schedule.cadence.record(at(0));
+        schedule.cadence.record(at(10));
+
+        assert_eq!(Some(7 * MINUTE), schedule.next_fetch(at(14), 15 * MINUTE));
+    }
+
+    #[test]
+    fn fetch_wait_bounded() {
+        let mut schedule = AdaptiveSchedule::new(MINUTE, 2 * MINUTE);
+        schedule.cadence.record(at(0));
+        schedule.cadence.record(at(10));
+
+        assert_eq!(
+            Some(2 * MINUTE),
+            schedule.next_fetch(at(30) + Duration::from_secs(30), 15 * MINUTE)
+        );
+        assert_eq!(Some(5 * MINUTE), schedule.next_fetch(at(10), 5 * MINUTE));
+    }
+}
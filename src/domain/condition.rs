@@ -0,0 +1,144 @@
+/// Coarse weather condition common to all providers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConditionCategory {
+    Clear,
+    Clouds,
+    Rain,
+    Snow,
+    Thunderstorm,
+    Fog,
+}
+
+impl ConditionCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConditionCategory::Clear => "clear",
+            ConditionCategory::Clouds => "clouds",
+            ConditionCategory::Rain => "rain",
+            ConditionCategory::Snow => "snow",
+            ConditionCategory::Thunderstorm => "thunderstorm",
+            ConditionCategory::Fog => "fog",
+        }
+    }
+
+    /// Category of an OpenWeatherMap condition ID
+    ///
+    /// See https://openweathermap.org/weather-conditions
+    pub fn from_openweathermap(id: u32) -> Option<Self> {
+        match id {
+            200..=299 => Some(ConditionCategory::Thunderstorm),
+            300..=599 => Some(ConditionCategory::Rain),
+            600..=699 => Some(ConditionCategory::Snow),
+            700..=799 => Some(ConditionCategory::Fog),
+            800 => Some(ConditionCategory::Clear),
+            801..=899 => Some(ConditionCategory::Clouds),
+            _ => None,
+        }
+    }
+
+    /// Category of a WMO weather interpretation code used by Open-Meteo
+    pub fn from_wmo_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(ConditionCategory::Clear),
+            1..=3 => Some(ConditionCategory::Clouds),
+            45 | 48 => Some(ConditionCategory::Fog),
+            51..=67 | 80..=82 => Some(ConditionCategory::Rain),
+            71..=77 | 85 | 86 => Some(ConditionCategory::Snow),
+            95..=99 => Some(ConditionCategory::Thunderstorm),
+            _ => None,
+        }
+    }
+
+    /// Category of a MET Norway symbol code, e.g. `lightrainshowers_day`
+    pub fn from_met_symbol(symbol: &str) -> Option<Self> {
+        let name = symbol.split('_').next().unwrap_or(symbol);
+
+        if name.contains("thunder") {
+            Some(ConditionCategory::Thunderstorm)
+        } else if name.contains("snow") || name.contains("sleet") {
+            Some(ConditionCategory::Snow)
+        } else if name.contains("rain") {
+            Some(ConditionCategory::Rain)
+        } else if name == "fog" {
+            Some(ConditionCategory::Fog)
+        } else if name == "clearsky" {
+            Some(ConditionCategory::Clear)
+        } else if name == "fair" || name.contains("cloudy") {
+            Some(ConditionCategory::Clouds)
+        } else {
+            None
+        }
+    }
+}
+
+/// Weather condition as reported by the provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    /// Provider specific code, OpenWeatherMap condition ID or WMO code
+    pub id: Option<u32>,
+    pub category: ConditionCategory,
+    /// Human readable description, localized where the provider supports it
+    pub description: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn openweathermap_groups() {
+        assert_eq!(
+            Some(ConditionCategory::Thunderstorm),
+            ConditionCategory::from_openweathermap(211)
+        );
+        assert_eq!(
+            Some(ConditionCategory::Rain),
+            ConditionCategory::from_openweathermap(300)
+        );
+        assert_eq!(
+            Some(ConditionCategory::Fog),
+            ConditionCategory::from_openweathermap(741)
+        );
+        assert_eq!(
+            Some(ConditionCategory::Clear),
+            ConditionCategory::from_openweathermap(800)
+        );
+        assert_eq!(
+            Some(ConditionCategory::Clouds),
+            ConditionCategory::from_openweathermap(804)
+        );
+    }
+
+    #[test]
+    fn wmo_codes() {
+        assert_eq!(
+            Some(ConditionCategory::Fog),
+            ConditionCategory::from_wmo_code(48)
+        );
+        assert_eq!(
+            Some(ConditionCategory::Snow),
+            ConditionCategory::from_wmo_code(86)
+        );
+        assert_eq!(None, ConditionCategory::from_wmo_code(4));
+    }
+
+    #[test]
+    fn met_symbols() {
+        assert_eq!(
+            Some(ConditionCategory::Rain),
+            ConditionCategory::from_met_symbol("lightrainshowers_day")
+        );
+        assert_eq!(
+            Some(ConditionCategory::Thunderstorm),
+            ConditionCategory::from_met_symbol("rainandthunder")
+        );
+        assert_eq!(
+            Some(ConditionCategory::Clouds),
+            ConditionCategory::from_met_symbol("partlycloudy_night")
+        );
+        assert_eq!(
+            Some(ConditionCategory::Clear),
+            ConditionCategory::from_met_symbol("clearsky_polartwilight")
+        );
+    }
+}
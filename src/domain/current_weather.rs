@@ -1,15 +1,31 @@
-use std::convert::Into;
+use std::convert::{Into, TryFrom};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use uom::si::f32::*;
-use uom::si::{pressure, thermodynamic_temperature};
+use uom::si::{length, pressure, thermodynamic_temperature, velocity};
 
-use crate::weather_types::{Main, WeatherReportCurrent};
+use crate::domain::condition::{Condition, ConditionCategory};
+use crate::domain::sun::SunTimes;
+use crate::met_norway_types::LocationForecast;
+use crate::open_meteo_types::ForecastCurrent;
+use crate::weather_types::{Main, Volume, WeatherReportCurrent};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CurrentWeather {
     temperature: ThermodynamicTemperature,
     pressure: Pressure,
+    station_pressure: Option<Pressure>,
     humidity: Humidity,
+    wind_speed: Option<Velocity>,
+    precipitation: Option<Length>,
+    utc_offset: Option<i32>,
+    coordinates: Option<(f32, f32)>,
+    sun_times: Option<SunTimes>,
+    condition: Option<Condition>,
+    observed_at: Option<SystemTime>,
+    location_name: Option<String>,
+    source: Option<String>,
+    contributions: Vec<CurrentWeather>,
 }
 
 impl CurrentWeather {
@@ -19,19 +35,264 @@ impl CurrentWeather {
                 temperature,
             ),
             pressure: Pressure::new::<pressure::hectopascal>(pressure),
+            station_pressure: None,
             humidity: Humidity::new(humidity),
+            wind_speed: None,
+            precipitation: None,
+            utc_offset: None,
+            coordinates: None,
+            sun_times: None,
+            condition: None,
+            observed_at: None,
+            location_name: None,
+            source: None,
+            contributions: Vec::new(),
         }
     }
+
+    pub fn from_quantities(
+        temperature: ThermodynamicTemperature,
+        pressure: Pressure,
+        humidity: Humidity,
+    ) -> Self {
+        CurrentWeather {
+            temperature,
+            pressure,
+            station_pressure: None,
+            humidity,
+            wind_speed: None,
+            precipitation: None,
+            utc_offset: None,
+            coordinates: None,
+            sun_times: None,
+            condition: None,
+            observed_at: None,
+            location_name: None,
+            source: None,
+            contributions: Vec::new(),
+        }
+    }
+
+    pub fn with_humidity(mut self, humidity: Humidity) -> Self {
+        self.humidity = humidity;
+        self
+    }
+
+    pub fn with_observed_at(mut self, observed_at: SystemTime) -> Self {
+        self.observed_at = Some(observed_at);
+        self
+    }
+
+    /// Pressure at the site altitude, not reduced to sea level
+    pub fn with_station_pressure(mut self, pressure: Pressure) -> Self {
+        self.station_pressure = Some(pressure);
+        self
+    }
+
+    pub fn with_wind_speed(mut self, wind_speed: Velocity) -> Self {
+        self.wind_speed = Some(wind_speed);
+        self
+    }
+
+    /// Amount of precipitation in an hour around the observation
+    pub fn with_precipitation(mut self, precipitation: Length) -> Self {
+        self.precipitation = Some(precipitation);
+        self
+    }
+
+    /// Offset of the local time at the location in seconds
+    pub fn with_utc_offset(mut self, utc_offset: i32) -> Self {
+        self.utc_offset = Some(utc_offset);
+        self
+    }
+
+    /// Latitude and longitude the observation belongs to
+    pub fn with_coordinates(mut self, latitude: f32, longitude: f32) -> Self {
+        self.coordinates = Some((latitude, longitude));
+        self
+    }
+
+    pub fn with_sun_times(mut self, sun_times: SunTimes) -> Self {
+        self.sun_times = Some(sun_times);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: Option<Condition>) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    /// Name of the place the provider reports the weather for
+    pub fn with_location_name(mut self, name: Option<String>) -> Self {
+        self.location_name = name;
+        self
+    }
+
+    pub fn set_source(&mut self, source: &str) {
+        self.source = Some(source.to_string());
+    }
+
+    /// Observations of individual providers this one was combined from
+    pub fn with_contributions(mut self, contributions: Vec<CurrentWeather>) -> Self {
+        self.contributions = contributions;
+        self
+    }
+}
+
+fn from_unix_timestamp(timestamp: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(timestamp)
 }
 
 impl From<Main> for CurrentWeather {
     fn from(main: Main) -> Self {
-        CurrentWeather::new(main.temp, main.pressure, main.humidity)
+        let weather = CurrentWeather::new(main.temp, main.pressure, main.humidity);
+
+        match main.grnd_level {
+            Some(level) => {
+                weather.with_station_pressure(Pressure::new::<pressure::hectopascal>(level))
+            }
+            None => weather,
+        }
     }
 }
 impl From<WeatherReportCurrent> for CurrentWeather {
     fn from(report: WeatherReportCurrent) -> Self {
-        report.main.into()
+        let precipitation = hourly_amount(&report.rain) + hourly_amount(&report.snow);
+        let condition = report.weather.into_iter().next().and_then(|w| {
+            ConditionCategory::from_openweathermap(w.id).map(|category| Condition {
+                id: Some(w.id),
+                category,
+                description: Some(w.description),
+            })
+        });
+        let weather: CurrentWeather = report.main.into();
+        let weather = weather
+            .with_wind_speed(Velocity::new::<velocity::meter_per_second>(
+                report.wind.speed,
+            ))
+            .with_precipitation(Length::new::<length::millimeter>(precipitation));
+        let weather = match report.timezone {
+            Some(offset) => weather.with_utc_offset(offset),
+            None => weather,
+        };
+
+        weather
+            .with_coordinates(report.coord.lat, report.coord.lon)
+            .with_sun_times(SunTimes {
+                sunrise: from_unix_timestamp(report.sys.sunrise),
+                sunset: from_unix_timestamp(report.sys.sunset),
+            })
+            .with_condition(condition)
+            .with_location_name(Some(report.name).filter(|name| !name.is_empty()))
+            .with_observed_at(from_unix_timestamp(report.dt))
+    }
+}
+
+/// OpenWeatherMap omits the volume when it does not rain
+fn hourly_amount(volume: &Option<Volume>) -> f32 {
+    match volume {
+        Some(Volume {
+            one_h: Some(amount),
+            ..
+        }) => *amount,
+        Some(Volume {
+            three_h: Some(amount),
+            ..
+        }) => *amount / 3.0,
+        _ => 0.0,
+    }
+}
+
+impl From<ForecastCurrent> for CurrentWeather {
+    fn from(forecast: ForecastCurrent) -> Self {
+        let current = forecast.current;
+
+        let weather = CurrentWeather::from_quantities(
+            ThermodynamicTemperature::new::<thermodynamic_temperature::degree_celsius>(
+                current.temperature_2m,
+            ),
+            Pressure::new::<pressure::hectopascal>(current.pressure_msl),
+            Humidity::new(current.relative_humidity_2m),
+        )
+        .with_observed_at(from_unix_timestamp(current.time))
+        .with_utc_offset(forecast.utc_offset_seconds)
+        .with_coordinates(forecast.latitude, forecast.longitude)
+        .with_condition(current.weather_code.and_then(|code| {
+            ConditionCategory::from_wmo_code(code).map(|category| Condition {
+                id: Some(code),
+                category,
+                description: None,
+            })
+        }));
+        let weather = match current.surface_pressure {
+            Some(surface) => {
+                weather.with_station_pressure(Pressure::new::<pressure::hectopascal>(surface))
+            }
+            None => weather,
+        };
+        let weather = match current.precipitation {
+            Some(amount) => weather.with_precipitation(Length::new::<length::millimeter>(amount)),
+            None => weather,
+        };
+
+        match current.wind_speed_10m {
+            Some(speed) => {
+                weather.with_wind_speed(Velocity::new::<velocity::meter_per_second>(speed))
+            }
+            None => weather,
+        }
+    }
+}
+
+impl TryFrom<LocationForecast> for CurrentWeather {
+    type Error = anyhow::Error;
+
+    fn try_from(forecast: LocationForecast) -> Result<Self, Self::Error> {
+        let step = forecast
+            .properties
+            .timeseries
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("MET Norway forecast contains no time steps"))?;
+        let next_hour = step.data.next_1_hours;
+        let condition = next_hour
+            .as_ref()
+            .and_then(|period| period.summary.as_ref())
+            .and_then(|summary| {
+                ConditionCategory::from_met_symbol(&summary.symbol_code).map(|category| Condition {
+                    id: None,
+                    category,
+                    description: None,
+                })
+            });
+        let precipitation = next_hour
+            .and_then(|period| period.details)
+            .and_then(|details| details.precipitation_amount);
+        let details = step.data.instant.details;
+        let time = chrono::DateTime::parse_from_rfc3339(&step.time)?;
+        let observed_at = from_unix_timestamp(time.timestamp().max(0) as u64);
+
+        let weather = CurrentWeather::from_quantities(
+            ThermodynamicTemperature::new::<thermodynamic_temperature::degree_celsius>(
+                details.air_temperature,
+            ),
+            Pressure::new::<pressure::hectopascal>(details.air_pressure_at_sea_level),
+            Humidity::new(details.relative_humidity),
+        )
+        .with_observed_at(observed_at)
+        .with_condition(condition);
+
+        let weather = match precipitation {
+            Some(amount) => weather.with_precipitation(Length::new::<length::millimeter>(amount)),
+            None => weather,
+        };
+
+        Ok(match details.wind_speed {
+            Some(speed) => {
+                weather.with_wind_speed(Velocity::new::<velocity::meter_per_second>(speed))
+            }
+            None => weather,
+        })
     }
 }
 
@@ -44,21 +305,69 @@ impl CurrentWeather {
         &self.pressure
     }
 
+    pub fn get_station_pressure(&self) -> Option<&Pressure> {
+        self.station_pressure.as_ref()
+    }
+
     pub fn get_humidity(&self) -> &Humidity {
         &self.humidity
     }
+
+    pub fn get_wind_speed(&self) -> Option<&Velocity> {
+        self.wind_speed.as_ref()
+    }
+
+    pub fn get_precipitation(&self) -> Option<&Length> {
+        self.precipitation.as_ref()
+    }
+
+    pub fn get_utc_offset(&self) -> Option<i32> {
+        self.utc_offset
+    }
+
+    pub fn get_coordinates(&self) -> Option<(f32, f32)> {
+        self.coordinates
+    }
+
+    pub fn get_sun_times(&self) -> Option<SunTimes> {
+        self.sun_times
+    }
+
+    pub fn get_condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
+    }
+
+    pub fn get_observed_at(&self) -> Option<SystemTime> {
+        self.observed_at
+    }
+
+    pub fn get_location_name(&self) -> Option<&str> {
+        self.location_name.as_deref()
+    }
+
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    pub fn get_contributions(&self) -> &[CurrentWeather] {
+        &self.contributions
+    }
+
+    /// Age of the observation, unknown when the provider does not report its time
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        self.observed_at
+            .map(|t| now.duration_since(t).unwrap_or_default())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Humidity {
     value: f32,
 }
 
 impl Humidity {
+    /// Relative humidity in percent, out of range values are left to the validation
     pub fn new(value: f32) -> Self {
-        assert!(value <= 100.0, "Humidity may be at most 100.0%.");
-        assert!(value >= 0.0, "Humidity must be at least 0%.");
-
         Humidity {
             value: Self::round(value),
         }
@@ -68,7 +377,6 @@ impl Humidity {
         (value * 100.0).round() / 100.0
     }
 
-    #[allow(dead_code)]
     pub fn is_valid(humidity: f32) -> bool {
         let rounded = Self::round(humidity);
         (0.0..=100.0).contains(&rounded)
@@ -156,6 +464,29 @@ mod test {
         let _: CurrentWeather = m.into();
     }
 
+    #[test]
+    fn ground_level_is_station_pressure() {
+        let m = Main {
+            temp: 283.0,
+            temp_min: 0.0,
+            temp_max: 0.0,
+            pressure: 1012.0,
+            sea_level: Some(1013.0),
+            grnd_level: Some(985.0),
+            humidity: 50.0,
+            temp_kf: None,
+        };
+        let weather: CurrentWeather = m.into();
+
+        let default = weather.get_pressure().get::<pressure::hectopascal>();
+        let station = weather
+            .get_station_pressure()
+            .unwrap()
+            .get::<pressure::hectopascal>();
+        assert!((1012.0 - default).abs() < 0.001, "{}", default);
+        assert!((985.0 - station).abs() < 0.001, "{}", station);
+    }
+
     #[test]
     fn humidity_ok() {
         let t = Humidity::new(32.0);
@@ -165,15 +496,10 @@ mod test {
     }
 
     #[test]
-    #[should_panic(expected = "Humidity may be at most 100.0%.")]
-    fn humidity_high() {
-        Humidity::new(132.0);
-    }
-
-    #[test]
-    #[should_panic(expected = "Humidity must be at least 0%.")]
-    fn humidity_low() {
-        Humidity::new(-1.0);
+    fn humidity_out_of_range_invalid() {
+        assert!(!Humidity::is_valid(132.0));
+        assert!(!Humidity::is_valid(-1.0));
+        assert!(!Humidity::is_valid(f32::NAN));
     }
 
     #[test]
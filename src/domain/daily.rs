@@ -0,0 +1,299 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use uom::si::f32::{Pressure, ThermodynamicTemperature};
+use uom::si::{pressure, thermodynamic_temperature};
+
+use crate::domain::current_weather::CurrentWeather;
+
+/// Minimum, maximum and running sum of a quantity
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    min: f32,
+    max: f32,
+    sum: f64,
+    count: u32,
+}
+
+impl Summary {
+    pub fn add(&mut self, value: f32) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.sum += f64::from(value);
+        self.count += 1;
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        self.known(self.min)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        self.known(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        self.known((self.sum / f64::from(self.count)) as f32)
+    }
+
+    fn known(&self, value: f32) -> Option<f32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+/// Summaries of the local day, in kelvins, pascals and percent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyStatistics {
+    /// Local date as YYYY-MM-DD
+    day: Option<String>,
+    /// Unix timestamp of the latest observation, repeated ones are not counted again
+    #[serde(default)]
+    last_observed: Option<u64>,
+    temperature: Summary,
+    humidity: Summary,
+    pressure: Summary,
+}
+
+impl DailyStatistics {
+    /// Adds the observation of the local day, a new day starts afresh
+    ///
+    /// Observations not newer than the latest one are skipped, providers repeat them until
+    /// they update.
+    pub fn record(
+        &mut self,
+        observed_at: SystemTime,
+        day: NaiveDate,
+        weather: &CurrentWeather,
+        pressure: Option<Pressure>,
+    ) {
+        let observed_at = unix_timestamp(observed_at);
+        match self.last_observed {
+            Some(last) if observed_at <= last => return,
+            _ => {}
+        }
+        self.roll_over(day);
+        self.last_observed = Some(observed_at);
+
+        self.temperature.add(
+            weather
+                .get_temperature()
+                .get::<thermodynamic_temperature::kelvin>(),
+        );
+        self.humidity.add(*weather.get_humidity().as_ref());
+        if let Some(pressure) = pressure {
+            self.pressure.add(pressure.get::<pressure::pascal>());
+        }
+    }
+
+    /// Starts the summaries of a new day, returns whether the day changed
+    pub fn roll_over(&mut self, day: NaiveDate) -> bool {
+        let day = day.format("%Y-%m-%d").to_string();
+        if self.day.as_deref() == Some(day.as_str()) {
+            return false;
+        }
+
+        *self = DailyStatistics {
+            day: Some(day),
+            last_observed: self.last_observed,
+            ..Default::default()
+        };
+        true
+    }
+
+    pub fn day(&self) -> Option<&str> {
+        self.day.as_deref()
+    }
+
+    pub fn temperature(&self) -> Vec<(&'static str, ThermodynamicTemperature)> {
+        statistics(&self.temperature)
+            .into_iter()
+            .map(|(name, v)| {
+                (
+                    name,
+                    ThermodynamicTemperature::new::<thermodynamic_temperature::kelvin>(v),
+                )
+            })
+            .collect()
+    }
+
+    pub fn humidity(&self) -> Vec<(&'static str, f32)> {
+        statistics(&self.humidity)
+    }
+
+    pub fn pressure(&self) -> Vec<(&'static str, Pressure)> {
+        statistics(&self.pressure)
+            .into_iter()
+            .map(|(name, v)| (name, Pressure::new::<pressure::pascal>(v)))
+            .collect()
+    }
+}
+
+fn statistics(summary: &Summary) -> Vec<(&'static str, f32)> {
+    vec![
+        ("min", summary.min()),
+        ("max", summary.max()),
+        ("mean", summary.mean()),
+    ]
+    .into_iter()
+    .filter_map(|(name, v)| v.map(|v| (name, v)))
+    .collect()
+}
+
+/// Date at the location, the configured zone wins over the offset reported by the provider
+pub fn local_date(at: SystemTime, zone: Option<Tz>, utc_offset: Option<i32>) -> NaiveDate {
+    let utc = Utc.timestamp(unix_timestamp(at) as i64, 0);
+
+    match (zone, utc_offset.and_then(FixedOffset::east_opt)) {
+        (Some(zone), _) => utc.with_timezone(&zone).date().naive_local(),
+        (None, Some(offset)) => utc.with_timezone(&offset).date().naive_local(),
+        (None, None) => utc.date().naive_local(),
+    }
+}
+
+/// Start of the next local day
+pub fn next_midnight(at: SystemTime, zone: Option<Tz>, utc_offset: Option<i32>) -> SystemTime {
+    let midnight = local_date(at, zone, utc_offset).succ().and_hms(0, 0, 0);
+    // Daylight saving time may skip midnight, the day then starts an hour later
+    let start = |local: NaiveDateTime| match (zone, utc_offset.and_then(FixedOffset::east_opt)) {
+        (Some(zone), _) => zone
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|t| t.timestamp()),
+        (None, Some(offset)) => offset
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|t| t.timestamp()),
+        (None, None) => Some(Utc.from_utc_datetime(&local).timestamp()),
+    };
+    let timestamp = start(midnight)
+        .or_else(|| start(midnight + chrono::Duration::hours(1)))
+        .unwrap_or_else(|| midnight.timestamp());
+
+    UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64)
+}
+
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(timestamp: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(timestamp)
+    }
+
+    #[test]
+    fn summary_of_values() {
+        let mut summary = Summary::default();
+        assert_eq!(None, summary.mean());
+
+        for v in &[3.0, -1.0, 4.0] {
+            summary.add(*v);
+        }
+
+        assert_eq!(Some(-1.0), summary.min());
+        assert_eq!(Some(4.0), summary.max());
+        assert_eq!(Some(2.0), summary.mean());
+    }
+
+    #[test]
+    fn date_in_zone() {
+        // 2024-06-30 22:30 UTC is already July in Prague
+        let time = at(1_719_786_600);
+
+        assert_eq!(
+            NaiveDate::from_ymd(2024, 6, 30),
+            local_date(time, None, None)
+        );
+        assert_eq!(
+            NaiveDate::from_ymd(2024, 7, 1),
+            local_date(time, Some(chrono_tz::Europe::Prague), None)
+        );
+        assert_eq!(
+            NaiveDate::from_ymd(2024, 7, 1),
+            local_date(time, None, Some(7200))
+        );
+    }
+
+    #[test]
+    fn midnight_rolls_over() {
+        let mut daily = DailyStatistics::default();
+        let first = NaiveDate::from_ymd(2024, 6, 30);
+        daily.record(
+            at(1),
+            first,
+            &CurrentWeather::new(290.0, 1010.0, 40.0),
+            None,
+        );
+        daily.record(
+            at(2),
+            first,
+            &CurrentWeather::new(300.0, 1010.0, 60.0),
+            None,
+        );
+
+        assert_eq!(
+            vec![("min", 40.0), ("max", 60.0), ("mean", 50.0)],
+            daily.humidity()
+        );
+
+        daily.record(
+            at(3),
+            first.succ(),
+            &CurrentWeather::new(280.0, 1010.0, 70.0),
+            None,
+        );
+        assert_eq!(Some("2024-07-01"), daily.day());
+        assert_eq!(
+            vec![("min", 70.0), ("max", 70.0), ("mean", 70.0)],
+            daily.humidity()
+        );
+        assert!(daily.pressure().is_empty());
+    }
+
+    #[test]
+    fn repeated_observation_skipped() {
+        let mut daily = DailyStatistics::default();
+        let day = NaiveDate::from_ymd(2024, 6, 30);
+        daily.record(at(2), day, &CurrentWeather::new(290.0, 1010.0, 40.0), None);
+        daily.record(at(2), day, &CurrentWeather::new(290.0, 1010.0, 40.0), None);
+        daily.record(at(1), day, &CurrentWeather::new(290.0, 1010.0, 70.0), None);
+
+        assert_eq!(
+            vec![("min", 40.0), ("max", 40.0), ("mean", 40.0)],
+            daily.humidity()
+        );
+
+        assert!(!daily.roll_over(day));
+        assert!(daily.roll_over(day.succ()));
+        assert!(daily.humidity().is_empty());
+        daily.record(at(2), day, &CurrentWeather::new(290.0, 1010.0, 40.0), None);
+        assert_eq!(Some("2024-07-01"), daily.day());
+    }
+
+    #[test]
+    fn next_midnight_in_zone() {
+        // 2024-06-30 12:00 UTC
+        let noon = at(1_719_748_800);
+
+        assert_eq!(at(1_719_792_000), next_midnight(noon, None, None));
+        assert_eq!(
+            at(1_719_784_800),
+            next_midnight(noon, Some(chrono_tz::Europe::Prague), None)
+        );
+        assert_eq!(at(1_719_784_800), next_midnight(noon, None, Some(7200)));
+    }
+}
@@ -0,0 +1,324 @@
+use std::str::FromStr;
+
+use uom::si::f32::{MassDensity, ThermodynamicTemperature, Velocity};
+use uom::si::{mass_density, thermodynamic_temperature, velocity};
+
+use crate::domain::current_weather::{CurrentWeather, Humidity};
+
+// Magnus formula coefficients over water, Sonntag 1990
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DerivedMetric {
+    DewPoint,
+    AbsoluteHumidity,
+    HeatIndex,
+    WindChill,
+    ApparentTemperature,
+    Humidex,
+}
+
+/// A derived value, temperatures are subject to unit conversion
+#[derive(Debug, Clone, Copy)]
+pub enum DerivedValue {
+    Temperature(ThermodynamicTemperature),
+    Density(MassDensity),
+    Index(f32),
+}
+
+impl DerivedMetric {
+    pub fn variants() -> Vec<&'static str> {
+        vec![
+            "dew-point",
+            "absolute-humidity",
+            "heat-index",
+            "wind-chill",
+            "apparent-temperature",
+            "humidex",
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DerivedMetric::DewPoint => "dew-point",
+            DerivedMetric::AbsoluteHumidity => "absolute-humidity",
+            DerivedMetric::HeatIndex => "heat-index",
+            DerivedMetric::WindChill => "wind-chill",
+            DerivedMetric::ApparentTemperature => "apparent-temperature",
+            DerivedMetric::Humidex => "humidex",
+        }
+    }
+
+    /// Computes the metric, `None` when the weather lacks an input or the metric is undefined
+    pub fn value(self, weather: &CurrentWeather) -> Option<DerivedValue> {
+        let temperature = *weather.get_temperature();
+        let humidity = weather.get_humidity();
+
+        match self {
+            DerivedMetric::DewPoint => {
+                dew_point(temperature, humidity).map(DerivedValue::Temperature)
+            }
+            DerivedMetric::AbsoluteHumidity => Some(DerivedValue::Density(absolute_humidity(
+                temperature,
+                humidity,
+            ))),
+            DerivedMetric::HeatIndex => {
+                Some(DerivedValue::Temperature(heat_index(temperature, humidity)))
+            }
+            DerivedMetric::WindChill => weather
+                .get_wind_speed()
+                .and_then(|wind| wind_chill(temperature, *wind))
+                .map(DerivedValue::Temperature),
+            DerivedMetric::ApparentTemperature => weather
+                .get_wind_speed()
+                .map(|wind| apparent_temperature(temperature, humidity, *wind))
+                .map(DerivedValue::Temperature),
+            DerivedMetric::Humidex => humidex(temperature, humidity).map(DerivedValue::Index),
+        }
+    }
+}
+
+impl FromStr for DerivedMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dew-point" => Ok(DerivedMetric::DewPoint),
+            "absolute-humidity" => Ok(DerivedMetric::AbsoluteHumidity),
+            "heat-index" => Ok(DerivedMetric::HeatIndex),
+            "wind-chill" => Ok(DerivedMetric::WindChill),
+            "apparent-temperature" => Ok(DerivedMetric::ApparentTemperature),
+            "humidex" => Ok(DerivedMetric::Humidex),
+            _ => anyhow::bail!("Unknown derived metric \"{}\"", s),
+        }
+    }
+}
+
+fn celsius(temperature: ThermodynamicTemperature) -> f32 {
+    temperature.get::<thermodynamic_temperature::degree_celsius>()
+}
+
+fn from_celsius(value: f32) -> ThermodynamicTemperature {
+    ThermodynamicTemperature::new::<thermodynamic_temperature::degree_celsius>(value)
+}
+
+/// Dew point by the Magnus formula, undefined for zero humidity
+pub fn dew_point(
+    temperature: ThermodynamicTemperature,
+    humidity: &Humidity,
+) -> Option<ThermodynamicTemperature> {
+    let rh: f32 = *humidity.as_ref();
+    if rh <= 0.0 {
+        return None;
+    }
+
+    let t = celsius(temperature);
+    let gamma = (rh / 100.0).ln() + MAGNUS_A * t / (MAGNUS_B + t);
+
+    Some(from_celsius(MAGNUS_B * gamma / (MAGNUS_A - gamma)))
+}
+
+/// Mass of water vapour in a cubic metre of air
+pub fn absolute_humidity(
+    temperature: ThermodynamicTemperature,
+    humidity: &Humidity,
+) -> MassDensity {
+    let t = celsius(temperature);
+    let rh: f32 = *humidity.as_ref();
+    let grams = 6.112 * (17.67 * t / (t + 243.5)).exp() * rh * 2.1674 / (273.15 + t);
+
+    MassDensity::new::<mass_density::gram_per_cubic_meter>(grams)
+}
+
+/// Heat index of the US National Weather Service, Rothfusz regression with adjustments
+pub fn heat_index(
+    temperature: ThermodynamicTemperature,
+    humidity: &Humidity,
+) -> ThermodynamicTemperature {
+    let t = temperature.get::<thermodynamic_temperature::degree_fahrenheit>();
+    let rh: f32 = *humidity.as_ref();
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let averaged = (simple + t) / 2.0;
+
+    let index = if averaged < 80.0 {
+        averaged
+    } else {
+        let mut index = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+            - 0.224_755_4 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            index -= ((13.0 - rh) / 4.0) * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            index += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+        }
+
+        index
+    };
+
+    ThermodynamicTemperature::new::<thermodynamic_temperature::degree_fahrenheit>(index)
+}
+
+/// Wind chill of the North American JAG/TI formula
+///
+/// Defined only for temperatures up to 10 °C and wind faster than 4.8 km/h.
+pub fn wind_chill(
+    temperature: ThermodynamicTemperature,
+    wind: Velocity,
+) -> Option<ThermodynamicTemperature> {
+    let t = celsius(temperature);
+    let v = wind.get::<velocity::kilometer_per_hour>();
+    if t > 10.0 || v <= 4.8 {
+        return None;
+    }
+
+    let factor = v.powf(0.16);
+    Some(from_celsius(
+        13.12 + 0.6215 * t - 11.37 * factor + 0.3965 * t * factor,
+    ))
+}
+
+/// Apparent temperature of the Australian Bureau of Meteorology, without solar radiation
+pub fn apparent_temperature(
+    temperature: ThermodynamicTemperature,
+    humidity: &Humidity,
+    wind: Velocity,
+) -> ThermodynamicTemperature {
+    let t = celsius(temperature);
+    let rh: f32 = *humidity.as_ref();
+    let vapour_pressure = rh / 100.0 * 6.105 * (17.27 * t / (237.7 + t)).exp();
+    let ws = wind.get::<velocity::meter_per_second>();
+
+    from_celsius(t + 0.33 * vapour_pressure - 0.70 * ws - 4.00)
+}
+
+/// Humidex of Environment Canada, a dimensionless number on the Celsius scale
+pub fn humidex(temperature: ThermodynamicTemperature, humidity: &Humidity) -> Option<f32> {
+    let dew_point = dew_point(temperature, humidity)?;
+    let td = dew_point.get::<thermodynamic_temperature::kelvin>();
+    let vapour_pressure = 6.11 * (5417.753 * (1.0 / 273.16 - 1.0 / td)).exp();
+
+    Some(celsius(temperature) + 0.5555 * (vapour_pressure - 10.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fahrenheit(value: f32) -> ThermodynamicTemperature {
+        ThermodynamicTemperature::new::<thermodynamic_temperature::degree_fahrenheit>(value)
+    }
+
+    fn kmh(value: f32) -> Velocity {
+        Velocity::new::<velocity::kilometer_per_hour>(value)
+    }
+
+    fn assert_close(expected: f32, actual: f32, tolerance: f32) {
+        assert!(
+            (expected - actual).abs() <= tolerance,
+            "{} should be {} ± {}",
+            actual,
+            expected,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn dew_point_table() {
+        for &(t, rh, expected) in &[(20.0, 50.0, 9.3), (30.0, 70.0, 23.9), (0.0, 90.0, -1.4)] {
+            let td = dew_point(from_celsius(t), &Humidity::new(rh)).unwrap();
+            assert_close(expected, celsius(td), 0.1);
+        }
+
+        assert!(dew_point(from_celsius(20.0), &Humidity::new(0.0)).is_none());
+    }
+
+    #[test]
+    fn absolute_humidity_table() {
+        for &(t, rh, expected) in &[(20.0, 50.0, 8.6), (30.0, 80.0, 24.3), (0.0, 100.0, 4.8)] {
+            let ah = absolute_humidity(from_celsius(t), &Humidity::new(rh));
+            assert_close(
+                expected,
+                ah.get::<mass_density::gram_per_cubic_meter>(),
+                0.1,
+            );
+        }
+    }
+
+    #[test]
+    fn heat_index_nws_table() {
+        for &(t, rh, expected) in &[
+            (90.0, 50.0, 95.0),
+            (100.0, 40.0, 109.0),
+            (86.0, 90.0, 105.0),
+        ] {
+            let index = heat_index(fahrenheit(t), &Humidity::new(rh));
+            assert_close(
+                expected,
+                index.get::<thermodynamic_temperature::degree_fahrenheit>(),
+                0.5,
+            );
+        }
+    }
+
+    #[test]
+    fn heat_index_of_mild_weather_is_close_to_temperature() {
+        let index = heat_index(fahrenheit(70.0), &Humidity::new(50.0));
+
+        assert_close(
+            69.5,
+            index.get::<thermodynamic_temperature::degree_fahrenheit>(),
+            0.1,
+        );
+    }
+
+    #[test]
+    fn wind_chill_environment_canada_table() {
+        for &(t, v, expected) in &[
+            (-10.0, 20.0, -18.0),
+            (0.0, 10.0, -3.0),
+            (-20.0, 30.0, -33.0),
+        ] {
+            let chill = wind_chill(from_celsius(t), kmh(v)).unwrap();
+            assert_close(expected, celsius(chill), 0.5);
+        }
+    }
+
+    #[test]
+    fn wind_chill_undefined_when_warm_or_calm() {
+        assert!(wind_chill(from_celsius(15.0), kmh(20.0)).is_none());
+        assert!(wind_chill(from_celsius(-5.0), kmh(3.0)).is_none());
+    }
+
+    #[test]
+    fn apparent_temperature_bom() {
+        let wind = Velocity::new::<velocity::meter_per_second>(2.0);
+        let at = apparent_temperature(from_celsius(25.0), &Humidity::new(50.0), wind);
+
+        assert_close(24.8, celsius(at), 0.1);
+    }
+
+    #[test]
+    fn humidex_environment_canada_table() {
+        // The table is indexed by dew point, 15 °C at 30 °C is 39.8 % of relative humidity
+        let index = humidex(from_celsius(30.0), &Humidity::new(39.8)).unwrap();
+        assert_close(34.0, index, 0.5);
+
+        let index = humidex(from_celsius(35.0), &Humidity::new(56.1)).unwrap();
+        assert_close(47.0, index, 0.5);
+    }
+
+    #[test]
+    fn metric_requires_wind() {
+        let weather = CurrentWeather::new(263.15, 1001.0, 55.1);
+
+        assert!(DerivedMetric::WindChill.value(&weather).is_none());
+        assert!(DerivedMetric::DewPoint.value(&weather).is_some());
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use slog::Logger;
+
+use crate::domain::current_weather::CurrentWeather;
+use crate::domain::interfaces::WeatherClient;
+
+pub struct NamedClient {
+    pub name: String,
+    pub client: Box<dyn WeatherClient + Send + Sync>,
+}
+
+/// Asks the providers in order until one returns a fresh observation
+///
+/// The observation is tagged with the name of the provider that served it.
+pub struct FailoverClient {
+    providers: Vec<NamedClient>,
+    max_age: Option<Duration>,
+    logger: Logger,
+}
+
+impl FailoverClient {
+    pub fn new(providers: Vec<NamedClient>, max_age: Option<Duration>, logger: Logger) -> Self {
+        FailoverClient {
+            providers,
+            max_age,
+            logger,
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherClient for FailoverClient {
+    async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+        let mut failures: Vec<String> = Vec::new();
+
+        for provider in &self.providers {
+            let failure = match provider.client.get_current_weather().await {
+                Ok(mut weather) => match stale_age(&weather, self.max_age, SystemTime::now()) {
+                    Some(age) => format!("observation is {} s old", age.as_secs()),
+                    None => {
+                        if !failures.is_empty() {
+                            slog::slog_warn!(self.logger, "Weather served by a fallback provider";
+                                "provider" => &provider.name);
+                        }
+                        weather.set_source(&provider.name);
+
+                        return Ok(weather);
+                    }
+                },
+                Err(e) => format!("{:#}", e),
+            };
+
+            slog::slog_warn!(self.logger, "Weather provider failed";
+                "provider" => &provider.name, "error" => &failure);
+            failures.push(format!("{}: {}", provider.name, failure));
+        }
+
+        anyhow::bail!("All weather providers failed ({})", failures.join("; "))
+    }
+}
+
+fn stale_age(
+    weather: &CurrentWeather,
+    max_age: Option<Duration>,
+    now: SystemTime,
+) -> Option<Duration> {
+    match (weather.age(now), max_age) {
+        (Some(age), Some(max_age)) if age > max_age => Some(age),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct FixedClient(Option<SystemTime>);
+
+    #[async_trait]
+    impl WeatherClient for FixedClient {
+        async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+            match self.0 {
+                Some(observed_at) => {
+                    Ok(CurrentWeather::new(283.3, 1001.0, 55.1).with_observed_at(observed_at))
+                }
+                None => anyhow::bail!("Provider is down"),
+            }
+        }
+    }
+
+    fn named(name: &str, observed_at: Option<SystemTime>) -> NamedClient {
+        NamedClient {
+            name: name.to_string(),
+            client: Box::new(FixedClient(observed_at)),
+        }
+    }
+
+    fn failover(providers: Vec<NamedClient>) -> FailoverClient {
+        let logger = Logger::root(slog::Discard, slog::o!());
+        FailoverClient::new(providers, Some(Duration::from_secs(3600)), logger)
+    }
+
+    #[tokio::test]
+    async fn primary_serves_when_healthy() {
+        let now = SystemTime::now();
+        let client = failover(vec![
+            named("primary", Some(now)),
+            named("backup", Some(now)),
+        ]);
+
+        let weather = client.get_current_weather().await.unwrap();
+        assert_eq!(Some("primary"), weather.get_source());
+    }
+
+    #[tokio::test]
+    async fn failed_primary_falls_back() {
+        let client = failover(vec![
+            named("primary", None),
+            named("backup", Some(SystemTime::now())),
+        ]);
+
+        let weather = client.get_current_weather().await.unwrap();
+        assert_eq!(Some("backup"), weather.get_source());
+    }
+
+    #[tokio::test]
+    async fn stale_primary_falls_back() {
+        let now = SystemTime::now();
+        let client = failover(vec![
+            named("primary", Some(now - Duration::from_secs(7200))),
+            named("backup", Some(now)),
+        ]);
+
+        let weather = client.get_current_weather().await.unwrap();
+        assert_eq!(Some("backup"), weather.get_source());
+    }
+
+    #[tokio::test]
+    async fn all_failures_reported() {
+        let client = failover(vec![named("primary", None), named("backup", None)]);
+
+        let error = client.get_current_weather().await.unwrap_err().to_string();
+        assert!(error.contains("primary: Provider is down"), "{}", error);
+        assert!(error.contains("backup: Provider is down"), "{}", error);
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::{Duration, SystemTime};
+
+use crate::domain::current_weather::CurrentWeather;
+
+/// Tracks whether the latest observation is too old to be trusted
+#[derive(Debug, Clone)]
+pub struct Freshness {
+    max_age: Duration,
+    /// Time of the latest observation, the start time before the first one
+    reference: SystemTime,
+}
+
+impl Freshness {
+    pub fn new(max_age: Duration, started: SystemTime) -> Self {
+        Freshness {
+            max_age,
+            reference: started,
+        }
+    }
+
+    /// Observations without their time are as fresh as their reception
+    pub fn update(&mut self, weather: &CurrentWeather, received: SystemTime) {
+        self.reference = weather.get_observed_at().unwrap_or(received);
+    }
+
+    pub fn stale_at(&self) -> SystemTime {
+        self.reference + self.max_age
+    }
+
+    pub fn is_stale(&self, now: SystemTime) -> bool {
+        now > self.stale_at()
+    }
+
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.reference).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000 + minutes * 60)
+    }
+
+    #[test]
+    fn stale_without_observations() {
+        let freshness = Freshness::new(HOUR, at(0));
+
+        assert!(!freshness.is_stale(at(60)));
+        assert!(freshness.is_stale(at(61)));
+    }
+
+    #[test]
+    fn old_observation_is_stale() {
+        let mut freshness = Freshness::new(HOUR, at(180));
+        freshness.update(
+            &CurrentWeather::new(283.3, 1001.0, 55.1).with_observed_at(at(0)),
+            at(180),
+        );
+
+        assert!(freshness.is_stale(at(180)));
+        assert_eq!(Duration::from_secs(3 * 3600), freshness.age(at(180)));
+    }
+
+    #[test]
+    fn reception_time_without_observation_time() {
+        let mut freshness = Freshness::new(HOUR, at(0));
+        freshness.update(&CurrentWeather::new(283.3, 1001.0, 55.1), at(100));
+
+        assert_eq!(at(160), freshness.stale_at());
+    }
+}
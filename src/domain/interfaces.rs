@@ -5,3 +5,13 @@ use async_trait::async_trait;
 pub trait WeatherClient {
     async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error>;
 }
+
+#[async_trait]
+impl<T> WeatherClient for Box<T>
+where
+    T: WeatherClient + Send + Sync + ?Sized,
+{
+    async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+        (**self).get_current_weather().await
+    }
+}
@@ -1,2 +1,14 @@
+pub mod blend;
+pub mod cadence;
+pub mod condition;
 pub mod current_weather;
+pub mod daily;
+pub mod derived;
+pub mod failover;
+pub mod freshness;
 pub mod interfaces;
+pub mod pressure;
+pub mod quota;
+pub mod sun;
+pub mod trend;
+pub mod validation;
@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+use uom::si::f32::{Length, Pressure};
+use uom::si::{length, thermodynamic_temperature};
+
+use crate::domain::current_weather::CurrentWeather;
+
+/// Temperature lapse rate of the standard atmosphere in K/m
+const LAPSE_RATE: f32 = 0.0065;
+/// g·M / (R·L) of the standard atmosphere
+const BAROMETRIC_EXPONENT: f32 = 5.257;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressureMode {
+    /// Reduced to mean sea level, comparable between locations
+    SeaLevel,
+    /// Actually measured at the site altitude
+    Station,
+}
+
+impl PressureMode {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["sea-level", "station"]
+    }
+}
+
+impl FromStr for PressureMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sea-level" => Ok(PressureMode::SeaLevel),
+            "station" => Ok(PressureMode::Station),
+            _ => anyhow::bail!("Unknown pressure mode \"{}\"", s),
+        }
+    }
+}
+
+/// Which pressure is published and the site altitude to compute it from
+#[derive(Debug, Clone, Copy)]
+pub struct PressureSettings {
+    pub mode: PressureMode,
+    pub altitude: Option<Length>,
+}
+
+impl PressureSettings {
+    /// Pressure in the configured mode, `None` when station pressure cannot be known
+    ///
+    /// Station pressure reported by the provider wins over the one computed from altitude.
+    pub fn pressure(&self, weather: &CurrentWeather) -> Option<Pressure> {
+        match self.mode {
+            PressureMode::SeaLevel => Some(*weather.get_pressure()),
+            PressureMode::Station => match weather.get_station_pressure() {
+                Some(pressure) => Some(*pressure),
+                None => self
+                    .altitude
+                    .map(|altitude| station_pressure(weather, altitude)),
+            },
+        }
+    }
+}
+
+impl Default for PressureSettings {
+    fn default() -> Self {
+        PressureSettings {
+            mode: PressureMode::SeaLevel,
+            altitude: None,
+        }
+    }
+}
+
+/// Sea-level pressure brought up to the altitude by the barometric formula
+///
+/// The observed temperature stands for the temperature at the site.
+pub fn station_pressure(weather: &CurrentWeather, altitude: Length) -> Pressure {
+    let h = altitude.get::<length::meter>();
+    let t = weather
+        .get_temperature()
+        .get::<thermodynamic_temperature::kelvin>();
+    let ratio = 1.0 - LAPSE_RATE * h / (t + LAPSE_RATE * h);
+
+    *weather.get_pressure() * ratio.powf(BAROMETRIC_EXPONENT)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use uom::si::f32::ThermodynamicTemperature;
+    use uom::si::pressure;
+
+    fn meters(value: f32) -> Length {
+        Length::new::<length::meter>(value)
+    }
+
+    #[test]
+    fn standard_atmosphere_at_altitude() {
+        // 15 °C at sea level is 8.5 °C at 1000 m of the standard atmosphere, 898.7 hPa
+        let weather = CurrentWeather::new(281.65, 1013.25, 50.0);
+        let p = station_pressure(&weather, meters(1000.0)).get::<pressure::hectopascal>();
+
+        assert!((898.7 - p).abs() < 0.5, "{}", p);
+    }
+
+    #[test]
+    fn reported_station_pressure_preferred() {
+        let weather = CurrentWeather::from_quantities(
+            ThermodynamicTemperature::new::<thermodynamic_temperature::kelvin>(283.0),
+            Pressure::new::<pressure::hectopascal>(1013.0),
+            crate::domain::current_weather::Humidity::new(50.0),
+        )
+        .with_station_pressure(Pressure::new::<pressure::hectopascal>(990.0));
+        let settings = PressureSettings {
+            mode: PressureMode::Station,
+            altitude: Some(meters(500.0)),
+        };
+
+        let p = settings
+            .pressure(&weather)
+            .unwrap()
+            .get::<pressure::hectopascal>();
+        assert!((990.0 - p).abs() < 0.001, "{}", p);
+    }
+
+    #[test]
+    fn station_pressure_unknown_without_altitude() {
+        let weather = CurrentWeather::new(283.0, 1013.0, 50.0);
+        let settings = PressureSettings {
+            mode: PressureMode::Station,
+            altitude: None,
+        };
+
+        assert!(settings.pressure(&weather).is_none());
+        assert!(PressureSettings::default().pressure(&weather).is_some());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+
+/// Calls of an API key in the current UTC day and month
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyUsage {
+    /// UTC date as YYYY-MM-DD
+    day: Option<String>,
+    calls_today: u32,
+    /// UTC month as YYYY-MM
+    month: Option<String>,
+    calls_this_month: u32,
+}
+
+impl KeyUsage {
+    /// Counts a call, a new day or month starts from zero
+    pub fn record(&mut self, at: SystemTime) {
+        let (day, month) = (day_label(at), month_label(at));
+
+        if self.day.as_ref() != Some(&day) {
+            self.day = Some(day);
+            self.calls_today = 0;
+        }
+        if self.month.as_ref() != Some(&month) {
+            self.month = Some(month);
+            self.calls_this_month = 0;
+        }
+        self.calls_today += 1;
+        self.calls_this_month += 1;
+    }
+
+    pub fn calls_today(&self, at: SystemTime) -> u32 {
+        if self.day.as_ref() == Some(&day_label(at)) {
+            self.calls_today
+        } else {
+            0
+        }
+    }
+
+    pub fn calls_this_month(&self, at: SystemTime) -> u32 {
+        if self.month.as_ref() == Some(&month_label(at)) {
+            self.calls_this_month
+        } else {
+            0
+        }
+    }
+}
+
+/// Call counts per API key, the keys are masked so that the state file does not leak them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiUsage {
+    keys: BTreeMap<String, KeyUsage>,
+}
+
+impl ApiUsage {
+    pub fn key(&mut self, label: &str) -> &mut KeyUsage {
+        self.keys.entry(label.to_string()).or_default()
+    }
+
+    pub fn get(&self, label: &str) -> Option<&KeyUsage> {
+        self.keys.get(label)
+    }
+}
+
+/// First and last four characters of the key, enough to tell keys apart
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "…".to_string();
+    }
+
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+/// Calls allowed to a single key, unlimited when unset
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QuotaBudget {
+    pub daily: Option<u32>,
+    pub monthly: Option<u32>,
+}
+
+impl QuotaBudget {
+    /// Shortest interval spreading the remaining calls over the rest of the day and month
+    pub fn required_interval(&self, usage: &KeyUsage, at: SystemTime) -> Duration {
+        let daily = self
+            .daily
+            .map(|budget| spread(budget, usage.calls_today(at), until_next_day(at)));
+        let monthly = self
+            .monthly
+            .map(|budget| spread(budget, usage.calls_this_month(at), until_next_month(at)));
+
+        daily.into_iter().chain(monthly).max().unwrap_or_default()
+    }
+
+    /// Used share of the budget closest to exhaustion
+    pub fn used_share(&self, usage: &KeyUsage, at: SystemTime) -> Option<f32> {
+        let daily = self
+            .daily
+            .map(|budget| usage.calls_today(at) as f32 / budget as f32);
+        let monthly = self
+            .monthly
+            .map(|budget| usage.calls_this_month(at) as f32 / budget as f32);
+
+        daily.into_iter().chain(monthly).fold(None, |max, share| {
+            Some(max.map_or(share, |max: f32| max.max(share)))
+        })
+    }
+}
+
+/// Exhausted budgets wait for the period to end
+fn spread(budget: u32, used: u32, remaining: Duration) -> Duration {
+    match budget.saturating_sub(used) {
+        0 => remaining,
+        left => remaining / left,
+    }
+}
+
+fn day_label(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at).format("%Y-%m-%d").to_string()
+}
+
+fn month_label(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at).format("%Y-%m").to_string()
+}
+
+fn until_next_day(at: SystemTime) -> Duration {
+    let now = DateTime::<Utc>::from(at);
+    let next = now.date().succ().and_hms(0, 0, 0);
+
+    next.signed_duration_since(now).to_std().unwrap_or_default()
+}
+
+fn until_next_month(at: SystemTime) -> Duration {
+    let now = DateTime::<Utc>::from(at);
+    let next = if now.month() == 12 {
+        Utc.ymd(now.year() + 1, 1, 1)
+    } else {
+        Utc.ymd(now.year(), now.month() + 1, 1)
+    };
+
+    next.and_hms(0, 0, 0)
+        .signed_duration_since(now)
+        .to_std()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    /// 2023-11-14 22:13:20 UTC
+    fn at(hours: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000) + HOUR * hours as u32
+    }
+
+    #[test]
+    fn counts_reset_with_new_day() {
+        let mut usage = KeyUsage::default();
+        usage.record(at(0));
+        usage.record(at(1));
+
+        assert_eq!(2, usage.calls_today(at(1)));
+        assert_eq!(0, usage.calls_today(at(2)));
+
+        usage.record(at(2));
+        assert_eq!(1, usage.calls_today(at(2)));
+        assert_eq!(3, usage.calls_this_month(at(2)));
+    }
+
+    #[test]
+    fn interval_stretched_by_budget() {
+        let mut usage = KeyUsage::default();
+        usage.record(at(0));
+        let budget = QuotaBudget {
+            daily: Some(11),
+            monthly: None,
+        };
+
+        // 1 h 46 min 40 s left of the day for 10 calls
+        assert_eq!(
+            Duration::from_secs(640),
+            budget.required_interval(&usage, at(0))
+        );
+        assert_eq!(Some(1.0 / 11.0), budget.used_share(&usage, at(0)));
+
+        let exhausted = QuotaBudget {
+            daily: Some(1),
+            monthly: Some(1000),
+        };
+        assert_eq!(
+            Duration::from_secs(6400),
+            exhausted.required_interval(&usage, at(0))
+        );
+    }
+
+    #[test]
+    fn keys_masked() {
+        assert_eq!("0123…cdef", mask_key("0123456789abcdef"));
+        assert_eq!("…", mask_key("short"));
+        assert_eq!(
+            Duration::default(),
+            QuotaBudget::default().required_interval(&KeyUsage::default(), at(0))
+        );
+    }
+}
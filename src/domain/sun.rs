@@ -0,0 +1,215 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::domain::current_weather::CurrentWeather;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Julian date of the Unix epoch
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+/// Julian date of the J2000 epoch, 2000-01-01 12:00
+const J2000: f64 = 2_451_545.0;
+/// Days from the Unix epoch to the J2000 epoch
+const J2000_DAY: i64 = 10_957;
+/// Altitude of the sun centre at sunrise, refraction and the solar disc radius included
+const SUNRISE_ALTITUDE: f64 = -0.833;
+const EARTH_TILT: f64 = 23.4397;
+
+/// Sunrise and sunset of a single day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunTimes {
+    pub sunrise: SystemTime,
+    pub sunset: SystemTime,
+}
+
+impl SunTimes {
+    pub fn is_daylight(&self, at: SystemTime) -> bool {
+        self.sunrise <= at && at < self.sunset
+    }
+
+    pub fn day_length(&self) -> Duration {
+        self.sunset.duration_since(self.sunrise).unwrap_or_default()
+    }
+
+    /// First sunrise or sunset after the time
+    pub fn next_transition(&self, after: SystemTime) -> Option<SystemTime> {
+        vec![self.sunrise, self.sunset]
+            .into_iter()
+            .find(|t| *t > after)
+    }
+}
+
+/// Sun times of the location, reported by the provider or computed from coordinates
+#[derive(Debug, Clone, Default)]
+pub struct Daylight {
+    reported: Option<SunTimes>,
+    coordinates: Option<(f32, f32)>,
+}
+
+impl Daylight {
+    pub fn new(coordinates: Option<(f32, f32)>) -> Self {
+        Daylight {
+            reported: None,
+            coordinates,
+        }
+    }
+
+    pub fn update(&mut self, weather: &CurrentWeather) {
+        self.reported = weather.get_sun_times();
+        if let Some(coordinates) = weather.get_coordinates() {
+            self.coordinates = Some(coordinates);
+        }
+    }
+
+    /// Sun times of the day the time falls in
+    pub fn times(&self, at: SystemTime) -> Option<SunTimes> {
+        let (latitude, longitude) = match self.coordinates {
+            Some(coordinates) => coordinates,
+            None => return self.reported,
+        };
+        let day = solar_day(at, longitude);
+
+        match self.reported {
+            Some(reported) if solar_day(reported.sunrise, longitude) == day => Some(reported),
+            _ => sun_times(day, latitude, longitude),
+        }
+    }
+
+    /// Unknown without coordinates or in polar regions
+    pub fn is_daylight(&self, at: SystemTime) -> Option<bool> {
+        self.times(at).map(|times| times.is_daylight(at))
+    }
+
+    /// Next sunrise or sunset, possibly of the following day
+    pub fn next_transition(&self, after: SystemTime) -> Option<SystemTime> {
+        if let Some(next) = self.times(after).and_then(|t| t.next_transition(after)) {
+            return Some(next);
+        }
+
+        let (latitude, longitude) = self.coordinates?;
+        sun_times(solar_day(after, longitude) + 1, latitude, longitude)
+            .and_then(|t| t.next_transition(after))
+    }
+}
+
+/// Day number since the Unix epoch of the local solar day at the longitude
+pub fn solar_day(at: SystemTime, longitude: f32) -> i64 {
+    let seconds = at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let shifted = seconds + f64::from(longitude) / 360.0 * SECONDS_PER_DAY;
+
+    (shifted / SECONDS_PER_DAY).floor() as i64
+}
+
+/// Sunrise and sunset by the sunrise equation, `None` during polar day or night
+///
+/// Precise to about a minute outside polar regions.
+pub fn sun_times(day: i64, latitude: f32, longitude: f32) -> Option<SunTimes> {
+    let latitude = f64::from(latitude);
+    let longitude = f64::from(longitude);
+
+    let n = (day - J2000_DAY) as f64;
+    let mean_noon = n - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_noon).rem_euclid(360.0);
+    let m = anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let l = ecliptic.to_radians();
+    let transit = J2000 + mean_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * l).sin();
+
+    let declination = (l.sin() * EARTH_TILT.to_radians().sin()).asin();
+    let phi = latitude.to_radians();
+    let cos_hour_angle = (SUNRISE_ALTITUDE.to_radians().sin() - phi.sin() * declination.sin())
+        / (phi.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    Some(SunTimes {
+        sunrise: from_julian(transit - hour_angle / 360.0),
+        sunset: from_julian(transit + hour_angle / 360.0),
+    })
+}
+
+fn from_julian(julian: f64) -> SystemTime {
+    let seconds = (julian - UNIX_EPOCH_JULIAN) * SECONDS_PER_DAY;
+    UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(timestamp: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(timestamp)
+    }
+
+    fn assert_near(expected: u64, actual: SystemTime) {
+        let actual = actual.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let difference = (expected as i64 - actual as i64).abs();
+        assert!(difference <= 120, "{} should be {}", actual, expected);
+    }
+
+    #[test]
+    fn prague_summer_solstice() {
+        // 2024-06-21, sunrise 04:52 and sunset 21:14 CEST
+        let day = solar_day(at(1_718_964_000), 14.42);
+        let times = sun_times(day, 50.08, 14.42).unwrap();
+
+        assert_near(1_718_938_320, times.sunrise);
+        assert_near(1_718_997_240, times.sunset);
+        assert!(times.is_daylight(at(1_718_964_000)));
+    }
+
+    #[test]
+    fn new_york_sunset_after_utc_midnight() {
+        // 2024-06-21, sunset 20:31 EDT is 00:31 UTC of the next day
+        let day = solar_day(at(1_718_985_600), -74.0);
+        let times = sun_times(day, 40.71, -74.0).unwrap();
+
+        assert_near(1_719_016_260, times.sunset);
+    }
+
+    #[test]
+    fn polar_day_has_no_sunset() {
+        let day = solar_day(at(1_718_964_000), 18.96);
+
+        assert!(sun_times(day, 69.65, 18.96).is_none());
+    }
+
+    #[test]
+    fn next_transition_of_tomorrow() {
+        let daylight = Daylight::new(Some((50.08, 14.42)));
+        // 2024-06-21 22:00 CEST, after the sunset
+        let evening = at(1_719_000_000);
+
+        assert_eq!(Some(false), daylight.is_daylight(evening));
+        assert_near(1_719_024_740, daylight.next_transition(evening).unwrap());
+    }
+
+    #[test]
+    fn reported_times_preferred() {
+        let mut daylight = Daylight::new(Some((50.08, 14.42)));
+        let reported = SunTimes {
+            sunrise: at(1_718_938_000),
+            sunset: at(1_718_997_000),
+        };
+        daylight.update(&CurrentWeather::new(290.0, 1010.0, 50.0).with_sun_times(reported));
+
+        assert_eq!(Some(reported), daylight.times(at(1_718_964_000)));
+    }
+
+    #[test]
+    fn transitions_in_order() {
+        let times = SunTimes {
+            sunrise: at(100),
+            sunset: at(200),
+        };
+
+        assert_eq!(Some(at(100)), times.next_transition(at(50)));
+        assert_eq!(Some(at(200)), times.next_transition(at(100)));
+        assert_eq!(None, times.next_transition(at(200)));
+        assert_eq!(Duration::from_secs(100), times.day_length());
+    }
+}
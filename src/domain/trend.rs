@@ -0,0 +1,329 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use uom::si::f32::Pressure;
+use uom::si::pressure;
+
+/// Changes over three hours bounding the tendency categories in hPa
+const STEADY: f32 = 0.1;
+const SLOW: f32 = 1.5;
+const NORMAL: f32 = 3.5;
+const QUICK: f32 = 6.0;
+
+const TENDENCY_PERIOD: Duration = Duration::from_secs(3 * 3600);
+
+/// Pressure tendency in the terms of shipping forecasts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tendency {
+    Steady,
+    RisingSlowly,
+    Rising,
+    RisingQuickly,
+    RisingVeryRapidly,
+    FallingSlowly,
+    Falling,
+    FallingQuickly,
+    FallingVeryRapidly,
+}
+
+impl Tendency {
+    /// Category of a change over three hours
+    pub fn from_change(change: Pressure) -> Self {
+        let hpa = change.get::<pressure::hectopascal>();
+        let magnitude = hpa.abs();
+
+        match (hpa > 0.0, magnitude) {
+            (_, m) if m < STEADY => Tendency::Steady,
+            (true, m) if m <= SLOW => Tendency::RisingSlowly,
+            (true, m) if m <= NORMAL => Tendency::Rising,
+            (true, m) if m <= QUICK => Tendency::RisingQuickly,
+            (true, _) => Tendency::RisingVeryRapidly,
+            (false, m) if m <= SLOW => Tendency::FallingSlowly,
+            (false, m) if m <= NORMAL => Tendency::Falling,
+            (false, m) if m <= QUICK => Tendency::FallingQuickly,
+            (false, _) => Tendency::FallingVeryRapidly,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tendency::Steady => "steady",
+            Tendency::RisingSlowly => "rising-slowly",
+            Tendency::Rising => "rising",
+            Tendency::RisingQuickly => "rising-quickly",
+            Tendency::RisingVeryRapidly => "rising-very-rapidly",
+            Tendency::FallingSlowly => "falling-slowly",
+            Tendency::Falling => "falling",
+            Tendency::FallingQuickly => "falling-quickly",
+            Tendency::FallingVeryRapidly => "falling-very-rapidly",
+        }
+    }
+}
+
+/// Period a pressure change is computed over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrendWindow {
+    pub period: Duration,
+    /// How much older than the period the reference observation may be, usually a fetch interval
+    pub tolerance: Duration,
+}
+
+impl TrendWindow {
+    pub fn new(period: Duration, tolerance: Duration) -> Self {
+        TrendWindow { period, tolerance }
+    }
+
+    /// Oldest observation time usable as the reference of a change ending at `latest`
+    fn oldest_reference(&self, latest: u64) -> u64 {
+        latest.saturating_sub((self.period + self.tolerance).as_secs())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureSample {
+    /// Unix timestamp of the observation
+    pub time: u64,
+    pub pascal: f32,
+}
+
+/// Recent pressure observations covering the trend window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PressureHistory {
+    samples: VecDeque<PressureSample>,
+}
+
+impl PressureHistory {
+    /// Adds an observation, a repeated observation time replaces the previous value
+    pub fn record(&mut self, at: SystemTime, value: Pressure, window: TrendWindow) {
+        let sample = PressureSample {
+            time: unix_timestamp(at),
+            pascal: value.get::<pressure::pascal>(),
+        };
+
+        match self.samples.back() {
+            Some(last) if last.time == sample.time => {
+                self.samples.pop_back();
+            }
+            Some(last) if last.time > sample.time => return,
+            _ => {}
+        }
+        self.samples.push_back(sample);
+
+        // Keep a single sample older than the window as the reference
+        let edge = sample.time.saturating_sub(window.period.as_secs());
+        while self.samples.len() > 1 && self.samples[1].time <= edge {
+            self.samples.pop_front();
+        }
+        self.prune(at, window);
+    }
+
+    /// Drops samples too old to be the reference of any later change, e.g. after an outage
+    pub fn prune(&mut self, now: SystemTime, window: TrendWindow) {
+        let oldest = window.oldest_reference(unix_timestamp(now));
+        self.samples.retain(|s| s.time >= oldest);
+    }
+
+    /// Change of the latest pressure against the one a window ago
+    ///
+    /// Unknown until the history spans the whole window and when the observation a window
+    /// ago is missing, an older one would stretch the change.
+    pub fn change(&self, window: TrendWindow) -> Option<Pressure> {
+        let (reference, latest) = self.span(window)?;
+
+        Some(Pressure::new::<pressure::pascal>(
+            latest.pascal - reference.pascal,
+        ))
+    }
+
+    /// Tendency of the change scaled to three hours
+    pub fn tendency(&self, window: TrendWindow) -> Option<Tendency> {
+        let change = self.change(window)?;
+
+        Some(Tendency::from_change(change * three_hour_scale(window)))
+    }
+
+    /// Characteristic of the pressure tendency, WMO code table 0200
+    ///
+    /// The halves of the window tell the shape of the curve, unknown without an observation
+    /// between them.
+    pub fn tendency_code(&self, window: TrendWindow) -> Option<u8> {
+        let (reference, latest) = self.span(window)?;
+        let middle_time = reference.time + (latest.time - reference.time) / 2;
+        let middle = self
+            .samples
+            .iter()
+            .filter(|s| s.time > reference.time && s.time < latest.time)
+            .min_by_key(|s| (s.time as i64 - middle_time as i64).abs())?;
+
+        let scale = three_hour_scale(window) / 100.0;
+        let first = (middle.pascal - reference.pascal) * scale;
+        let second = (latest.pascal - middle.pascal) * scale;
+
+        Some(characteristic(first, second))
+    }
+
+    /// The reference observation a window before the latest one and the latest one
+    fn span(&self, window: TrendWindow) -> Option<(&PressureSample, &PressureSample)> {
+        let latest = self.samples.back()?;
+        let edge = latest.time.checked_sub(window.period.as_secs())?;
+        let reference = self.samples.iter().rev().find(|s| s.time <= edge)?;
+
+        if reference.time < window.oldest_reference(latest.time) {
+            return None;
+        }
+        Some((reference, latest))
+    }
+}
+
+/// WMO code of the changes over the first and second half of the window in hPa per three hours
+fn characteristic(first: f32, second: f32) -> u8 {
+    let total = first + second;
+    let rising = |change: f32| change >= STEADY;
+    let falling = |change: f32| change <= -STEADY;
+
+    if total.abs() < STEADY {
+        return match (
+            rising(first),
+            falling(first),
+            rising(second),
+            falling(second),
+        ) {
+            (true, _, _, true) => 0,
+            (_, true, true, _) => 5,
+            _ => 4,
+        };
+    }
+
+    if total > 0.0 {
+        if rising(first) && falling(second) {
+            0
+        } else if rising(first) && !rising(second) || second < first - STEADY {
+            1
+        } else if !rising(first) || second > first + STEADY {
+            3
+        } else {
+            2
+        }
+    } else if falling(first) && rising(second) {
+        5
+    } else if falling(first) && !falling(second) || second > first + STEADY {
+        6
+    } else if !falling(first) || second < first - STEADY {
+        8
+    } else {
+        7
+    }
+}
+
+fn three_hour_scale(window: TrendWindow) -> f32 {
+    TENDENCY_PERIOD.as_secs_f32() / window.period.as_secs_f32()
+}
+
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WINDOW: TrendWindow = TrendWindow {
+        period: Duration::from_secs(3 * 3600),
+        tolerance: Duration::from_secs(600),
+    };
+
+    fn hpa(value: f32) -> Pressure {
+        Pressure::new::<pressure::hectopascal>(value)
+    }
+
+    fn at(hours: f32) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_secs_f32(hours * 3600.0)
+    }
+
+    #[test]
+    fn tendency_categories() {
+        assert_eq!(Tendency::Steady, Tendency::from_change(hpa(0.05)));
+        assert_eq!(Tendency::RisingSlowly, Tendency::from_change(hpa(1.0)));
+        assert_eq!(Tendency::Falling, Tendency::from_change(hpa(-2.0)));
+        assert_eq!(Tendency::FallingQuickly, Tendency::from_change(hpa(-4.0)));
+        assert_eq!(Tendency::RisingVeryRapidly, Tendency::from_change(hpa(7.0)));
+    }
+
+    #[test]
+    fn change_unknown_until_window_covered() {
+        let mut history = PressureHistory::default();
+        history.record(at(0.0), hpa(1010.0), WINDOW);
+        history.record(at(2.0), hpa(1008.0), WINDOW);
+
+        assert!(history.change(WINDOW).is_none());
+    }
+
+    #[test]
+    fn change_against_window_start() {
+        let mut history = PressureHistory::default();
+        for (hour, value) in &[(0.0, 1012.0), (1.0, 1011.0), (2.0, 1010.0), (3.0, 1009.5)] {
+            history.record(at(*hour), hpa(*value), WINDOW);
+        }
+
+        let change = history
+            .change(WINDOW)
+            .unwrap()
+            .get::<pressure::hectopascal>();
+        assert!((-2.5 - change).abs() < 0.01, "{}", change);
+        assert_eq!(Some(Tendency::Falling), history.tendency(WINDOW));
+    }
+
+    #[test]
+    fn old_samples_pruned() {
+        let mut history = PressureHistory::default();
+        for hour in 0..10 {
+            history.record(at(hour as f32), hpa(1000.0), WINDOW);
+        }
+
+        assert_eq!(4, history.samples.len());
+    }
+
+    #[test]
+    fn stale_reference_rejected() {
+        let mut history = PressureHistory::default();
+        history.record(at(0.0), hpa(1012.0), WINDOW);
+        history.record(at(5.0), hpa(1010.0), WINDOW);
+
+        assert!(history.change(WINDOW).is_none());
+        assert_eq!(1, history.samples.len());
+
+        let mut loaded = PressureHistory::default();
+        loaded.record(at(0.0), hpa(1012.0), WINDOW);
+        loaded.prune(at(3.5), WINDOW);
+        assert!(loaded.samples.is_empty());
+    }
+
+    #[test]
+    fn tendency_codes() {
+        let mut history = PressureHistory::default();
+        for (hour, value) in &[(0.0, 1010.0), (1.5, 1012.0), (3.0, 1011.0)] {
+            history.record(at(*hour), hpa(*value), WINDOW);
+        }
+        assert_eq!(Some(0), history.tendency_code(WINDOW));
+
+        assert_eq!(2, characteristic(1.0, 1.0));
+        assert_eq!(1, characteristic(1.5, 0.0));
+        assert_eq!(3, characteristic(-0.5, 1.5));
+        assert_eq!(4, characteristic(0.0, 0.05));
+        assert_eq!(5, characteristic(-1.0, 1.0));
+        assert_eq!(6, characteristic(-1.5, 0.0));
+        assert_eq!(7, characteristic(-1.0, -1.0));
+        assert_eq!(8, characteristic(0.0, -1.5));
+    }
+
+    #[test]
+    fn repeated_observation_replaced() {
+        let mut history = PressureHistory::default();
+        history.record(at(0.0), hpa(1000.0), WINDOW);
+        history.record(at(0.0), hpa(1001.0), WINDOW);
+
+        assert_eq!(1, history.samples.len());
+    }
+}
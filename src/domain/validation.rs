@@ -0,0 +1,221 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use uom::si::f32::{Pressure, ThermodynamicTemperature};
+use uom::si::{pressure, thermodynamic_temperature};
+
+use crate::domain::current_weather::{CurrentWeather, Humidity};
+
+/// Humidity this far outside 0–100 % is a rounding error and gets clamped
+const HUMIDITY_TOLERANCE: f32 = 5.0;
+/// Sea-level pressure records are about 870 and 1084 hPa
+const MIN_PRESSURE_HPA: f32 = 850.0;
+const MAX_PRESSURE_HPA: f32 = 1100.0;
+
+/// Bounds of a plausible observation
+#[derive(Debug, Clone, Copy)]
+pub struct PlausibilityLimits {
+    pub min_temperature: ThermodynamicTemperature,
+    pub max_temperature: ThermodynamicTemperature,
+    /// Largest change of the sea-level pressure between two fetches
+    pub max_pressure_step: Option<Pressure>,
+    /// Fetch period the pressure step relates to
+    pub interval: Duration,
+}
+
+impl Default for PlausibilityLimits {
+    fn default() -> Self {
+        PlausibilityLimits {
+            min_temperature: ThermodynamicTemperature::new::<
+                thermodynamic_temperature::degree_celsius,
+            >(-90.0),
+            max_temperature: ThermodynamicTemperature::new::<
+                thermodynamic_temperature::degree_celsius,
+            >(60.0),
+            max_pressure_step: Some(Pressure::new::<pressure::hectopascal>(5.0)),
+            interval: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Reason an observation was not published
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    Humidity(f32),
+    Temperature(f32),
+    Pressure(f32),
+    PressureSpike { change: f32, allowed: f32 },
+}
+
+impl Rejection {
+    /// Quantity the rejection is counted under
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Rejection::Humidity(_) => "humidity",
+            Rejection::Temperature(_) => "temperature",
+            Rejection::Pressure(_) | Rejection::PressureSpike { .. } => "pressure",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Humidity(value) => write!(f, "humidity {} % out of range", value),
+            Rejection::Temperature(value) => write!(f, "temperature {} °C out of bounds", value),
+            Rejection::Pressure(value) => write!(f, "pressure {} hPa out of range", value),
+            Rejection::PressureSpike { change, allowed } => write!(
+                f,
+                "pressure changed by {} hPa, at most {} hPa allowed",
+                change, allowed
+            ),
+        }
+    }
+}
+
+/// Checks observations against the limits and the last accepted pressure
+pub struct Validator {
+    limits: PlausibilityLimits,
+    last_pressure: Option<(SystemTime, Pressure)>,
+}
+
+impl Validator {
+    pub fn new(limits: PlausibilityLimits) -> Self {
+        Validator {
+            limits,
+            last_pressure: None,
+        }
+    }
+
+    /// Returns the observation with slightly out of range humidity clamped
+    ///
+    /// A rejected observation does not become the reference of the pressure step.
+    pub fn check(
+        &mut self,
+        weather: CurrentWeather,
+        now: SystemTime,
+    ) -> Result<CurrentWeather, Rejection> {
+        let humidity = *weather.get_humidity().as_ref();
+        let weather = if Humidity::is_valid(humidity) {
+            weather
+        } else if (-HUMIDITY_TOLERANCE..=100.0 + HUMIDITY_TOLERANCE).contains(&humidity) {
+            weather.with_humidity(Humidity::new(humidity.clamp(0.0, 100.0)))
+        } else {
+            return Err(Rejection::Humidity(humidity));
+        };
+
+        let temperature = *weather.get_temperature();
+        if !(self.limits.min_temperature..=self.limits.max_temperature).contains(&temperature) {
+            return Err(Rejection::Temperature(
+                temperature.get::<thermodynamic_temperature::degree_celsius>(),
+            ));
+        }
+
+        let pressure = *weather.get_pressure();
+        let hpa = pressure.get::<pressure::hectopascal>();
+        if !(MIN_PRESSURE_HPA..=MAX_PRESSURE_HPA).contains(&hpa) {
+            return Err(Rejection::Pressure(hpa));
+        }
+
+        let observed_at = weather.get_observed_at().unwrap_or(now);
+        if let (Some(step), Some((last_at, last))) =
+            (self.limits.max_pressure_step, self.last_pressure)
+        {
+            // A longer gap since the last accepted observation allows a larger change
+            let elapsed = observed_at.duration_since(last_at).unwrap_or_default();
+            let intervals = (elapsed.as_secs_f32() / self.limits.interval.as_secs_f32()).max(1.0);
+            let change = (pressure - last).get::<pressure::hectopascal>();
+            let allowed = step.get::<pressure::hectopascal>() * intervals;
+
+            if change.abs() > allowed {
+                return Err(Rejection::PressureSpike { change, allowed });
+            }
+        }
+        self.last_pressure = Some((observed_at, pressure));
+
+        Ok(weather)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000 + minutes * 60)
+    }
+
+    fn observation(celsius: f32, hpa: f32, humidity: f32, minutes: u64) -> CurrentWeather {
+        CurrentWeather::new(celsius + 273.15, hpa, humidity).with_observed_at(at(minutes))
+    }
+
+    #[test]
+    fn plausible_observation_accepted() {
+        let mut validator = Validator::new(PlausibilityLimits::default());
+
+        assert!(validator
+            .check(observation(12.0, 1013.0, 55.0, 0), at(0))
+            .is_ok());
+    }
+
+    #[test]
+    fn humidity_clamped_or_rejected() {
+        let mut validator = Validator::new(PlausibilityLimits::default());
+
+        let clamped = validator
+            .check(observation(12.0, 1013.0, 100.4, 0), at(0))
+            .unwrap();
+        assert_eq!(100.0, *clamped.get_humidity().as_ref());
+
+        assert_eq!(
+            Err(Rejection::Humidity(132.0)),
+            validator
+                .check(observation(12.0, 1013.0, 132.0, 10), at(10))
+                .map(|_| ())
+        );
+        assert!(validator
+            .check(observation(12.0, 1013.0, f32::NAN, 20), at(20))
+            .is_err());
+    }
+
+    #[test]
+    fn temperature_outside_bounds_rejected() {
+        let mut validator = Validator::new(PlausibilityLimits::default());
+
+        let rejection = validator
+            .check(observation(-120.0, 1013.0, 55.0, 0), at(0))
+            .unwrap_err();
+        assert_eq!("temperature", rejection.kind());
+    }
+
+    #[test]
+    fn pressure_spike_rejected() {
+        let mut validator = Validator::new(PlausibilityLimits::default());
+        validator
+            .check(observation(12.0, 1013.0, 55.0, 0), at(0))
+            .unwrap();
+
+        let rejection = validator
+            .check(observation(12.0, 1025.0, 55.0, 10), at(10))
+            .unwrap_err();
+        assert_eq!("pressure", rejection.kind());
+
+        // The spike is not the reference, a regular change still passes
+        assert!(validator
+            .check(observation(12.0, 1012.0, 55.0, 20), at(20))
+            .is_ok());
+    }
+
+    #[test]
+    fn pressure_step_grows_with_gap() {
+        let mut validator = Validator::new(PlausibilityLimits::default());
+        validator
+            .check(observation(12.0, 1013.0, 55.0, 0), at(0))
+            .unwrap();
+
+        assert!(validator
+            .check(observation(12.0, 1025.0, 55.0, 30), at(30))
+            .is_ok());
+    }
+}
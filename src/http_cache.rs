@@ -0,0 +1,372 @@
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use reqwest::header::{HeaderMap, CACHE_CONTROL, ETAG, LAST_MODIFIED};
+use url::Url;
+
+use crate::app::status::StatusBoard;
+
+/// Query parameters holding API keys, never written to the cache
+const KEY_PARAMS: [&str; 2] = ["appid", "apikey"];
+
+/// Response of a GET request as kept on disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// Request URL without the API key
+    url: String,
+    pub body: String,
+    /// Unix timestamp of the response or of its latest revalidation
+    stored_at: u64,
+    /// Freshness lifetime in seconds from `Cache-Control: max-age`
+    max_age: Option<u64>,
+    /// `Cache-Control: no-cache`, every use has to be revalidated
+    no_cache: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Directives of the `Cache-Control` header the cache follows
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheControl {
+    pub max_age: Option<u64>,
+    pub no_cache: bool,
+    pub no_store: bool,
+}
+
+impl CacheControl {
+    pub fn parse(value: &str) -> Self {
+        let mut control = CacheControl::default();
+
+        for directive in value.split(',').map(str::trim) {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap_or("").to_ascii_lowercase();
+            match (name.as_str(), parts.next()) {
+                ("max-age", Some(secs)) => control.max_age = secs.trim_matches('"').parse().ok(),
+                ("no-cache", _) => control.no_cache = true,
+                ("no-store", _) => control.no_store = true,
+                _ => {}
+            }
+        }
+
+        control
+    }
+}
+
+/// What the cache had for a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    /// Usable without asking the server
+    Fresh(CachedResponse),
+    /// Usable once the server confirms it has not changed
+    Stale(CachedResponse),
+    Miss,
+}
+
+/// How a request was served, counted in the status report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheOutcome {
+    Hit,
+    Revalidated,
+    Miss,
+}
+
+impl CacheOutcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            CacheOutcome::Hit => "hit",
+            CacheOutcome::Revalidated => "revalidated",
+            CacheOutcome::Miss => "miss",
+        }
+    }
+}
+
+/// Directory of cached responses surviving restarts
+///
+/// Responses stay fresh for their `Cache-Control` max-age, never longer than the configured
+/// maximum. Daemons of the same location may share the directory.
+#[derive(Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    max_age: Duration,
+    status: StatusBoard,
+}
+
+impl HttpCache {
+    pub fn new(dir: &Path, max_age: Duration, status: StatusBoard) -> Self {
+        HttpCache {
+            dir: dir.to_path_buf(),
+            max_age,
+            status,
+        }
+    }
+
+    pub fn record(&self, outcome: CacheOutcome) {
+        self.status.record_cache_outcome(outcome);
+    }
+
+    /// Unreadable entries are misses, the cache must never fail a request
+    pub fn lookup(&self, url: &Url, now: SystemTime) -> Lookup {
+        let body = match fs::read_to_string(self.path(url)) {
+            Ok(body) => body,
+            Err(_) => return Lookup::Miss,
+        };
+        let entry: CachedResponse = match serde_json::from_str(&body) {
+            Ok(entry) => entry,
+            Err(_) => return Lookup::Miss,
+        };
+        // Hash collisions are unlikely, but a foreign response would be wrong data
+        if entry.url != strip_keys(url) {
+            return Lookup::Miss;
+        }
+
+        if self.is_fresh(&entry, now) {
+            Lookup::Fresh(entry)
+        } else {
+            Lookup::Stale(entry)
+        }
+    }
+
+    /// Keeps the response unless the server forbids it, returns whether it was stored
+    pub fn store(
+        &self,
+        url: &Url,
+        body: String,
+        headers: &HeaderMap,
+        now: SystemTime,
+    ) -> Result<bool, anyhow::Error> {
+        let control = cache_control(headers);
+        if control.no_store {
+            self.remove(url)?;
+            return Ok(false);
+        }
+
+        let entry = CachedResponse {
+            url: strip_keys(url),
+            body,
+            stored_at: unix_timestamp(now),
+            max_age: control.max_age,
+            no_cache: control.no_cache,
+            etag: header(headers, ETAG),
+            last_modified: header(headers, LAST_MODIFIED),
+        };
+        self.write(url, &entry)?;
+
+        Ok(true)
+    }
+
+    /// Restarts the freshness of an entry the server answered 304 Not Modified for
+    pub fn revalidated(
+        &self,
+        url: &Url,
+        entry: &mut CachedResponse,
+        headers: &HeaderMap,
+        now: SystemTime,
+    ) -> Result<(), anyhow::Error> {
+        let control = cache_control(headers);
+
+        entry.stored_at = unix_timestamp(now);
+        if headers.contains_key(CACHE_CONTROL) {
+            entry.max_age = control.max_age;
+            entry.no_cache = control.no_cache;
+        }
+        if let Some(etag) = header(headers, ETAG) {
+            entry.etag = Some(etag);
+        }
+        if let Some(last_modified) = header(headers, LAST_MODIFIED) {
+            entry.last_modified = Some(last_modified);
+        }
+        self.write(url, entry)
+    }
+
+    fn is_fresh(&self, entry: &CachedResponse, now: SystemTime) -> bool {
+        let lifetime = match entry.max_age {
+            Some(secs) => Duration::from_secs(secs).min(self.max_age),
+            None => self.max_age,
+        };
+        let age = now
+            .duration_since(UNIX_EPOCH + Duration::from_secs(entry.stored_at))
+            .unwrap_or_default();
+
+        !entry.no_cache && age < lifetime
+    }
+
+    /// Replaces the file at once so that other daemons never read it half written
+    fn write(&self, url: &Url, entry: &CachedResponse) -> Result<(), anyhow::Error> {
+        let path = self.path(url);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        let body = serde_json::to_string(entry)?;
+
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&temporary, body))
+            .and_then(|_| fs::rename(&temporary, &path))
+            .with_context(|| format!("Cannot write cache file {}", path.display()))
+    }
+
+    fn remove(&self, url: &Url) -> Result<(), anyhow::Error> {
+        let path = self.path(url);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Cannot remove cache file {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, url: &Url) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(strip_keys(url).as_bytes())))
+    }
+}
+
+impl fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("dir", &self.dir)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
+
+/// The URL identifying the cached response, API keys are left out
+pub fn strip_keys(url: &Url) -> String {
+    let mut stripped = url.clone();
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !KEY_PARAMS.contains(&name.to_ascii_lowercase().as_str()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    if params.is_empty() {
+        stripped.set_query(None);
+    } else {
+        stripped.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    stripped.into_string()
+}
+
+/// FNV-1a, unlike the standard hasher it is stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn cache_control(headers: &HeaderMap) -> CacheControl {
+    header(headers, CACHE_CONTROL)
+        .map(|value| CacheControl::parse(&value))
+        .unwrap_or_default()
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000) + MINUTE * minutes as u32
+    }
+
+    fn url(key: &str) -> Url {
+        Url::parse_with_params(
+            "https://api.openweathermap.org/data/2.5/weather",
+            &[("id", "3067696"), ("APPID", key), ("units", "metric")],
+        )
+        .unwrap()
+    }
+
+    fn cache(name: &str) -> HttpCache {
+        let dir =
+            std::env::temp_dir().join(format!("outdoor-cache-{}-{}", name, std::process::id()));
+        HttpCache::new(&dir, 10 * MINUTE, StatusBoard::new())
+    }
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn api_key_left_out() {
+        assert_eq!(
+            "https://api.openweathermap.org/data/2.5/weather?id=3067696&units=metric",
+            strip_keys(&url("secret"))
+        );
+    }
+
+    #[test]
+    fn cache_control_parsed() {
+        assert_eq!(
+            CacheControl {
+                max_age: Some(300),
+                no_cache: true,
+                no_store: false,
+            },
+            CacheControl::parse("public, Max-Age=300, no-cache")
+        );
+    }
+
+    #[test]
+    fn fresh_within_max_age_across_keys() {
+        let cache = cache("fresh");
+        let headers = headers(&[(CACHE_CONTROL, "max-age=300"), (ETAG, "\"v1\"")]);
+        assert_eq!(Lookup::Miss, cache.lookup(&url("a"), at(0)));
+
+        assert!(cache
+            .store(&url("a"), "{}".to_string(), &headers, at(0))
+            .unwrap());
+
+        match cache.lookup(&url("b"), at(4)) {
+            Lookup::Fresh(entry) => assert_eq!(Some("\"v1\"".to_string()), entry.etag),
+            other => panic!("Expected a fresh entry, got {:?}", other),
+        }
+        let mut stale = match cache.lookup(&url("b"), at(6)) {
+            Lookup::Stale(entry) => entry,
+            other => panic!("Expected a stale entry, got {:?}", other),
+        };
+
+        cache
+            .revalidated(&url("b"), &mut stale, &HeaderMap::new(), at(6))
+            .unwrap();
+        assert!(matches!(cache.lookup(&url("a"), at(7)), Lookup::Fresh(_)));
+
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn no_store_not_cached() {
+        let cache = cache("no-store");
+        let headers = headers(&[(CACHE_CONTROL, "no-store")]);
+
+        assert!(!cache
+            .store(&url("a"), "{}".to_string(), &headers, at(0))
+            .unwrap());
+        assert_eq!(Lookup::Miss, cache.lookup(&url("a"), at(0)));
+
+        if cache.dir.exists() {
+            fs::remove_dir_all(&cache.dir).unwrap();
+        }
+    }
+}
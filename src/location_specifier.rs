@@ -37,23 +37,23 @@ impl<'a> LocationSpecifier<'a> {
     pub fn format(&'a self) -> Vec<(String, String)> {
         match &self {
             LocationSpecifier::CityAndCountryName { city, country } => {
-                return if country.is_empty() {
+                if country.is_empty() {
                     vec![("q".to_string(), (*city).to_string())]
                 } else {
                     vec![("q".to_string(), format!("{},{}", city, country))]
                 }
             }
             LocationSpecifier::CityId(id) => {
-                return vec![("id".to_string(), (*id).to_string())];
+                vec![("id".to_string(), (*id).to_string())]
             }
             LocationSpecifier::Coordinates { lat, lon } => {
-                return vec![
+                vec![
                     ("lat".to_string(), format!("{}", lat)),
                     ("lon".to_string(), format!("{}", lon)),
-                ];
+                ]
             }
             LocationSpecifier::ZipCode { zip, country } => {
-                return if country.is_empty() {
+                if country.is_empty() {
                     vec![("zip".to_string(), (*zip).to_string())]
                 } else {
                     vec![("zip".to_string(), format!("{},{}", zip, country))]
@@ -66,27 +66,27 @@ impl<'a> LocationSpecifier<'a> {
                 lat_top,
                 zoom,
             } => {
-                return vec![(
+                vec![(
                     "bbox".to_string(),
                     format!(
                         "{},{},{},{},{}",
                         lon_left, lat_bottom, lon_right, lat_top, zoom
                     ),
-                )];
+                )]
             }
             LocationSpecifier::Circle { lat, lon, count } => {
-                return vec![
+                vec![
                     ("lat".to_string(), format!("{}", lat)),
                     ("lon".to_string(), format!("{}", lon)),
                     ("cnt".to_string(), format!("{}", count)),
-                ];
+                ]
             }
             LocationSpecifier::CityIds(ids) => {
                 let mut locations: String = "".to_string();
                 for loc in ids {
                     locations += loc;
                 }
-                return vec![("id".to_string(), locations)];
+                vec![("id".to_string(), locations)]
             }
         }
     }
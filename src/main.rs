@@ -1,4 +1,5 @@
 extern crate anyhow;
+extern crate chrono_tz;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -8,7 +9,7 @@ extern crate sloggers;
 extern crate uom;
 extern crate url;
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use rumq_client::eventloop;
 use rumq_client::MqttOptions;
@@ -17,82 +18,194 @@ use tokio::sync::mpsc::channel;
 use domain::current_weather;
 use location_specifier::LocationSpecifier;
 
-use crate::app::publisher::{Humidity, Pressure, Temperature};
+use crate::app::commands::{CommandHandler, CommandTopics};
+use crate::app::health::{run_health_server, HealthLimits};
+use crate::app::once::{publish_messages, render, table, Message, PrintFormat};
+use crate::app::publisher::WeatherTopics;
+use crate::app::quota::QuotaTracker;
+use crate::app::state::{PublisherState, StateFile};
+use crate::app::status::StatusBoard;
 use crate::app::tasks::*;
-use crate::arguments::MqttConnectionArgs;
+use crate::arguments::{
+    Args, CheckArgs, CityCommand, CitySearchArgs, Cli, Command, ConfigCommand, MqttConnectionArgs,
+    OnceArgs, Provider, ProviderArgs, ProviderMode, Units,
+};
+use crate::city_search::{search_city_list, CityQuery, GeocodingClientBuilder};
+use crate::domain::blend::{BlendClient, BlendSettings, WeightedClient};
+use crate::domain::failover::{FailoverClient, NamedClient};
+use crate::domain::freshness::Freshness;
+use crate::domain::interfaces::WeatherClient;
+use crate::domain::pressure::PressureMode;
+use crate::domain::quota::ApiUsage;
+use crate::domain::sun::Daylight;
+use crate::domain::validation::Validator;
+use crate::http_cache::HttpCache;
+use crate::met_norway_client::MetNorwayClientBuilder;
+use crate::open_meteo_client::OpenMeteoClientBuilder;
 use crate::weather_client::OpenWeatherMapClientBuilder;
 use std::sync::Arc;
+use url::Url;
 
 mod app;
 mod arguments;
+mod city_search;
 mod domain;
+mod http_cache;
 mod location_specifier;
+mod met_norway_client;
+mod met_norway_types;
+mod open_meteo_client;
+mod open_meteo_types;
 mod weather_client;
 mod weather_types;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let settings: arguments::Args = structopt::StructOpt::from_args();
+    let cli = Cli::from_args_or_run();
 
-    let logger = Arc::new(app::logging::create_logger(settings.verbose)?);
+    let (logger, _log_guard) = app::logging::create_logger(
+        cli.verbose,
+        cli.logging.log_format,
+        cli.logging.log_destination,
+        cli.logging.log_file.as_deref(),
+    )?;
+    let logger = Arc::new(logger);
 
-    let city_id = settings.city_id.to_string();
-    let api_key = settings.api_key;
+    match cli.command {
+        Some(Command::Run(settings)) => run(*settings, logger).await,
+        Some(Command::Once(once)) => run_once(*once, logger).await,
+        Some(Command::City(CityCommand::Search(search))) => run_city_search(search, logger).await,
+        Some(Command::Config(ConfigCommand::Check(check))) => run_config_check(check, logger).await,
+        None => unreachable!("Arguments without a command are parsed as the run command"),
+    }
+}
+
+/// Fetches and publishes the weather until a task fails
+async fn run(settings: Args, logger: Arc<slog::Logger>) -> Result<(), anyhow::Error> {
     let period = Duration::from_secs(settings.interval_secs.get().into());
-    let api_base = settings.api_base;
+    let status = StatusBoard::new();
 
     let (weather_tx, weather_rx) = channel::<current_weather::CurrentWeather>(10);
+    let (commands_tx, commands_rx) = channel(1);
 
-    let mut builder =
-        OpenWeatherMapClientBuilder::new(LocationSpecifier::CityId(city_id.as_ref()), api_key);
-    if let Some(base) = api_base {
-        builder.with_base_url(base);
-    }
+    let fetcher_logger = Arc::new(logger.new(slog::o!(
+        "location" => settings.provider.location_label(),
+    )));
+
+    let mut history = create_history(&settings, &logger);
+    let quota = create_quota(
+        &settings,
+        history.state.api_usage.clone(),
+        status.clone(),
+        &fetcher_logger,
+    );
+    history.quota = quota.clone();
 
-    let api_client = builder.build()?;
+    let cache = settings.http_cache_dir.as_deref().map(|dir| {
+        HttpCache::new(
+            dir,
+            Duration::from_secs(settings.http_cache_max_age_secs.into()),
+            status.clone(),
+        )
+    });
+
+    let api_client = create_provider_client(
+        &settings.provider,
+        settings.units,
+        quota.clone(),
+        cache,
+        (*fetcher_logger).clone(),
+    )?;
 
     let weather_fetcher = {
-        let mut builder = WeatherFetcherBuilder::new(weather_tx, api_client, logger.clone());
+        let mut builder =
+            WeatherFetcherBuilder::new(weather_tx, api_client, fetcher_logger, status.clone());
 
         if settings.abort_on_api_error {
             builder.set_error_behaviour(OnErrorBehaviour::Abort);
         }
 
+        builder.set_validator(Validator::new(settings.plausibility_limits()));
+        builder.set_commands(commands_rx);
+        if let Some(schedule) = settings.adaptive_schedule() {
+            builder.set_adaptive_schedule(schedule);
+        }
+        if let Some(quota) = &quota {
+            builder.set_quota(quota.clone());
+        }
+
         builder.build_task(period)
     };
 
     let weather_handle = tokio::spawn(weather_fetcher);
 
-    let (requests_tx, requests_rx) = channel(10);
+    let (mut requests_tx, requests_rx) = channel(10);
 
-    let mqtt_options = create_connection_options(settings.mqtt_connection);
-    let eventloop = eventloop(mqtt_options, requests_rx);
+    let format = settings.payload_format();
+    let stale_after = settings.stale_after();
 
-    let units = settings.units;
+    let dry_run = settings.dry_run;
+    let mqtt_options = create_connection_options(settings.mqtt_connection);
 
-    let temperature = Temperature::from_publishing_args(&settings.publishing);
-    let pressure = Pressure::from_publishing_args(&settings.publishing);
-    let humidity = Humidity::from_publishing_args(&settings.publishing);
+    let topics = WeatherTopics::from_publishing_args(&settings.publishing);
 
     let publisher_task = create_mqtt_publisher(
         weather_rx,
-        temperature,
+        topics,
         requests_tx.clone(),
-        pressure,
-        humidity,
-        units,
+        format,
+        history,
+        status.clone(),
         logger.clone(),
     );
 
     let handle_mqtt = tokio::spawn(publisher_task);
 
-    let handle_mqtt_loop = tokio::spawn(run_mqtt_loop(eventloop, logger.clone()));
+    let handle_mqtt_loop = if dry_run {
+        slog::slog_warn!(logger, "Dry run, messages are logged instead of published");
+        // The log sink stands in for the broker, readiness depends on fresh weather only
+        status.set_mqtt_connected(true);
+        tokio::spawn(run_log_sink(requests_rx, logger.clone()))
+    } else {
+        let command_topics = CommandTopics::from_publishing_args(&settings.publishing);
+        requests_tx.send(command_topics.subscribe_request()).await?;
+        let commands = CommandHandler {
+            topics: command_topics,
+            fetcher_tx: commands_tx,
+            requests_tx: requests_tx.clone(),
+            max_interval: period,
+            quota: quota.clone(),
+        };
+
+        let eventloop = eventloop(mqtt_options, requests_rx);
+        tokio::spawn(run_mqtt_loop(
+            eventloop,
+            commands,
+            logger.clone(),
+            status.clone(),
+        ))
+    };
+
+    let health_limits = HealthLimits {
+        liveness: period * 2,
+        readiness: period * settings.ready_intervals.get(),
+        staleness: stale_after,
+    };
+    let http_listen = settings.http_listen;
+    let health_logger = logger.clone();
+    let handle_health = tokio::spawn(async move {
+        match http_listen {
+            Some(address) => run_health_server(address, status, health_limits, health_logger).await,
+            None => futures_util::future::pending().await,
+        }
+    });
 
     let error_msg: Option<String>;
     tokio::select!(
         v = weather_handle => {error_msg = Some(format!("Weather fetcher finished: {:?}", v));},
         v = handle_mqtt => {error_msg = Some(format!("Publisher task finished: {:?}", v));},
         v = handle_mqtt_loop => {error_msg = Some(format!("MQTT loop finished: {:?}", v));},
+        v = handle_health => {error_msg = Some(format!("HTTP status server finished: {:?}", v));},
     );
 
     match error_msg {
@@ -101,6 +214,365 @@ async fn main() -> Result<(), anyhow::Error> {
     }
 }
 
+/// Fetches the weather once, prints it and optionally publishes it
+async fn run_once(once: OnceArgs, logger: Arc<slog::Logger>) -> Result<(), anyhow::Error> {
+    let settings = once.settings;
+    let mut history = create_history(&settings, &logger);
+    let quota = create_quota(
+        &settings,
+        history.state.api_usage.clone(),
+        StatusBoard::new(),
+        &logger,
+    );
+    let api_client = create_provider_client(
+        &settings.provider,
+        settings.units,
+        quota.clone(),
+        None,
+        (*logger).clone(),
+    )?;
+    let weather = api_client.get_current_weather().await;
+    if let Err(e) = save_api_usage(&settings, quota.as_ref()) {
+        slog::slog_warn!(logger, "API usage not saved"; "error" => format!("{:#}", e));
+    }
+    let weather = weather?;
+    let now = SystemTime::now();
+    let weather = Validator::new(settings.plausibility_limits())
+        .check(weather, now)
+        .map_err(|rejection| anyhow::anyhow!("Implausible weather: {}", rejection))?;
+
+    // The history is not saved, a single fetch must not disturb the daemon one
+    let format = settings.payload_format();
+    history.record(&weather, &format, now);
+
+    let topics = WeatherTopics::from_publishing_args(&settings.publishing);
+    let (live, retained) = observation_messages(&weather, &history, &topics, &format, now);
+    let messages = Message::from_batches(live, retained);
+    println!("{}", render(&weather, &messages, once.format)?);
+
+    if once.publish {
+        publish_messages(
+            create_connection_options(settings.mqtt_connection.with_client_suffix("once")),
+            &messages,
+        )
+        .await?;
+        slog::slog_info!(logger, "Weather published"; "messages" => messages.len());
+    }
+
+    Ok(())
+}
+
+/// Checks the settings and the services they point to, reports every problem found
+async fn run_config_check(
+    check: CheckArgs,
+    logger: Arc<slog::Logger>,
+) -> Result<(), anyhow::Error> {
+    let (settings, mut problems) = check.settings();
+    if let Some(settings) = settings {
+        problems.extend(settings.problems());
+
+        let usage = match settings.state_file.as_deref().map(StateFile::new) {
+            Some(file) => file
+                .load()
+                .map(|state| state.api_usage)
+                .unwrap_or_else(|e| {
+                    problems.push(format!("State file: {:#}", e));
+                    Default::default()
+                }),
+            None => Default::default(),
+        };
+        let quota = create_quota(&settings, usage, StatusBoard::new(), &logger);
+        match create_provider_client(
+            &settings.provider,
+            settings.units,
+            quota.clone(),
+            None,
+            (*logger).clone(),
+        ) {
+            Ok(api_client) => {
+                match api_client.get_current_weather().await {
+                    Ok(_) => println!("Weather API: ok"),
+                    Err(e) => problems.push(format!("Weather API: {:#}", e)),
+                }
+                if let Err(e) = save_api_usage(&settings, quota.as_ref()) {
+                    problems.push(format!("State file: {:#}", e));
+                }
+            }
+            Err(e) => problems.push(format!("Weather provider: {:#}", e)),
+        }
+
+        if !CheckArgs::is_missing(&settings.mqtt_connection.mqtt_host) {
+            let mqtt_options =
+                create_connection_options(settings.mqtt_connection.with_client_suffix("check"));
+            match publish_messages(mqtt_options, &[]).await {
+                Ok(()) => println!("MQTT broker: ok"),
+                Err(e) => problems.push(format!("MQTT broker: {:#}", e)),
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        println!("Configuration is valid");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("- {}", problem);
+    }
+    anyhow::bail!("{} configuration problem(s) found", problems.len())
+}
+
+/// Prints cities matching the search from the city list or the geocoding API
+async fn run_city_search(
+    search: CitySearchArgs,
+    logger: Arc<slog::Logger>,
+) -> Result<(), anyhow::Error> {
+    let query = CityQuery {
+        name: search.name,
+        country: search.country,
+        limit: search.limit.get() as usize,
+    };
+
+    let cities = match &search.city_list {
+        Some(path) => search_city_list(std::fs::File::open(path)?, &query)?,
+        None => {
+            let api_key = search.api_key.ok_or_else(|| {
+                anyhow::anyhow!("The geocoding API requires an API key, or use --city-list")
+            })?;
+            let mut builder = GeocodingClientBuilder::new(api_key);
+            builder.with_logger((*logger).clone());
+            builder.build()?.search(&query).await?
+        }
+    };
+    if cities.is_empty() {
+        anyhow::bail!("No city \"{}\" found", query.name);
+    }
+
+    match search.format {
+        PrintFormat::Json => println!("{}", serde_json::to_string_pretty(&cities)?),
+        PrintFormat::Table => {
+            let mut rows = vec![vec![
+                "ID".to_string(),
+                "NAME".to_string(),
+                "STATE".to_string(),
+                "COUNTRY".to_string(),
+                "LATITUDE".to_string(),
+                "LONGITUDE".to_string(),
+            ]];
+            rows.extend(cities.into_iter().map(|city| {
+                vec![
+                    city.id.map_or_else(|| "-".to_string(), |id| id.to_string()),
+                    city.name,
+                    city.state.unwrap_or_else(|| "-".to_string()),
+                    city.country,
+                    city.latitude.to_string(),
+                    city.longitude.to_string(),
+                ]
+            }));
+            print!("{}", table(&rows));
+        }
+    }
+
+    Ok(())
+}
+
+fn create_history(settings: &Args, logger: &slog::Logger) -> PublisherHistory {
+    if settings.pressure_mode == PressureMode::Station && settings.altitude.is_none() {
+        slog::slog_warn!(
+            logger,
+            "Station pressure is published only when the provider reports it, set the altitude"
+        );
+    }
+
+    let state_file = settings.state_file.as_deref().map(StateFile::new);
+    let mut state = match &state_file {
+        Some(file) => file.load().unwrap_or_else(|e| {
+            slog::slog_warn!(logger, "Publisher state not loaded, starting afresh"; "error" => format!("{:#}", e));
+            PublisherState::default()
+        }),
+        None => PublisherState::default(),
+    };
+    let trend_window = settings.pressure_trend_window();
+    state
+        .pressure_history
+        .prune(SystemTime::now(), trend_window);
+
+    PublisherHistory {
+        state,
+        state_file,
+        trend_window,
+        timezone: settings.timezone,
+        utc_offset: None,
+        daylight: Daylight::new(settings.provider.coordinates()),
+        freshness: Freshness::new(settings.stale_after(), SystemTime::now()),
+        quota: None,
+        started: tokio::time::Instant::now(),
+    }
+}
+
+/// Counts the calls of the OpenWeatherMap key against its budget, from the given counts on
+fn create_quota(
+    settings: &Args,
+    usage: ApiUsage,
+    status: StatusBoard,
+    logger: &slog::Logger,
+) -> Option<QuotaTracker> {
+    settings.provider.api_key.clone().map(|key| {
+        QuotaTracker::new(
+            usage,
+            &String::from(key),
+            settings.quota_budget(),
+            status,
+            logger.clone(),
+        )
+    })
+}
+
+/// Stores the calls of a one-off command in the state file, the rest of the state is kept
+fn save_api_usage(settings: &Args, quota: Option<&QuotaTracker>) -> Result<(), anyhow::Error> {
+    match (quota, settings.state_file.as_deref()) {
+        (Some(quota), Some(path)) => {
+            let file = StateFile::new(path);
+            let mut state = file.load()?;
+            state.api_usage = quota.usage();
+            file.save(&state)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn create_provider_client(
+    providers: &ProviderArgs,
+    units: Units,
+    quota: Option<QuotaTracker>,
+    cache: Option<HttpCache>,
+    logger: slog::Logger,
+) -> Result<Box<dyn WeatherClient + Send + Sync>, anyhow::Error> {
+    let mut clients = Vec::new();
+
+    for (position, kind) in providers.providers.iter().enumerate() {
+        // The custom base URL makes sense only for a single provider
+        let api_base = if position == 0 {
+            providers.api_base.clone()
+        } else {
+            None
+        };
+        let provider_logger = logger.new(slog::o!("provider" => kind.name()));
+
+        clients.push(NamedClient {
+            name: kind.name().to_string(),
+            client: create_weather_client(
+                *kind,
+                providers,
+                units,
+                api_base,
+                quota.clone(),
+                cache.clone(),
+                provider_logger,
+            )?,
+        });
+    }
+
+    match providers.provider_mode {
+        ProviderMode::Failover => Ok(Box::new(FailoverClient::new(
+            clients,
+            providers.max_observation_age(),
+            logger,
+        ))),
+        ProviderMode::Blend => {
+            let weighted = clients
+                .into_iter()
+                .zip(providers.weights()?)
+                .map(|(client, weight)| WeightedClient { client, weight })
+                .collect();
+            let settings = BlendSettings {
+                method: providers.blend_method,
+                outlier_threshold: providers.outlier_threshold,
+                max_age: providers.max_observation_age(),
+            };
+
+            Ok(Box::new(BlendClient::new(weighted, settings, logger)))
+        }
+    }
+}
+
+fn create_weather_client(
+    kind: Provider,
+    provider: &ProviderArgs,
+    units: Units,
+    api_base: Option<Url>,
+    quota: Option<QuotaTracker>,
+    cache: Option<HttpCache>,
+    logger: slog::Logger,
+) -> Result<Box<dyn WeatherClient + Send + Sync>, anyhow::Error> {
+    match kind {
+        Provider::OpenWeatherMap => {
+            let api_key = provider
+                .api_key
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("OpenWeatherMap requires an API key"))?;
+            let city_id = provider.city_id.map(|id| id.to_string());
+            let location = match (&city_id, provider.coordinates()) {
+                (Some(id), _) => LocationSpecifier::CityId(id),
+                (None, Some((lat, lon))) => LocationSpecifier::Coordinates { lat, lon },
+                (None, None) => {
+                    anyhow::bail!("OpenWeatherMap requires a city ID or latitude and longitude")
+                }
+            };
+
+            let mut builder = OpenWeatherMapClientBuilder::new(location, api_key);
+            builder.with_logger(logger);
+            builder.with_units(units.unit_system());
+            if let Some(quota) = quota {
+                builder.with_quota(quota);
+            }
+            if let Some(cache) = cache {
+                builder.with_cache(cache);
+            }
+            if let Some(language) = &provider.lang {
+                builder.with_language(language.clone());
+            }
+            if let Some(base) = api_base {
+                builder.with_base_url(base);
+            }
+
+            Ok(Box::new(builder.build()?))
+        }
+        Provider::OpenMeteo => {
+            let (lat, lon) = provider
+                .coordinates()
+                .ok_or_else(|| anyhow::anyhow!("Open-Meteo requires latitude and longitude"))?;
+
+            let mut builder = OpenMeteoClientBuilder::new(lat, lon);
+            builder.with_logger(logger);
+            if let Some(base) = api_base {
+                builder.with_base_url(base);
+            }
+
+            Ok(Box::new(builder.build()?))
+        }
+        Provider::MetNorway => {
+            let (lat, lon) = provider
+                .coordinates()
+                .ok_or_else(|| anyhow::anyhow!("MET Norway requires latitude and longitude"))?;
+            let user_agent = provider.user_agent.clone().unwrap_or_else(|| {
+                format!(
+                    "outdoor/{} https://github.com/RadekDvorak/outdoor",
+                    env!("CARGO_PKG_VERSION")
+                )
+            });
+
+            let mut builder = MetNorwayClientBuilder::new(lat, lon, user_agent);
+            builder.with_logger(logger);
+            if let Some(base) = api_base {
+                builder.with_base_url(base);
+            }
+
+            Ok(Box::new(builder.build()?))
+        }
+    }
+}
+
 fn create_connection_options(mqtt_connection: MqttConnectionArgs) -> MqttOptions {
     let mut mqtt_options = MqttOptions::new(
         mqtt_connection.mqtt_id,
@@ -0,0 +1,240 @@
+use std::convert::TryInto;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Instant, SystemTime};
+
+use async_trait::async_trait;
+use reqwest::header::{EXPIRES, IF_MODIFIED_SINCE, LAST_MODIFIED};
+use reqwest::StatusCode;
+use slog::Logger;
+use url::Url;
+
+use crate::domain::current_weather::CurrentWeather;
+use crate::domain::interfaces::WeatherClient;
+use crate::met_norway_types::LocationForecast;
+
+/// Last successful response kept to honour `Expires` and `Last-Modified`
+///
+/// MET Norway terms of service forbid repeated requests before the data expires.
+struct CachedForecast {
+    weather: CurrentWeather,
+    last_modified: Option<String>,
+    expires: Option<SystemTime>,
+}
+
+impl CachedForecast {
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        match self.expires {
+            Some(expires) => now < expires,
+            None => false,
+        }
+    }
+}
+
+pub struct MetNorwayClient {
+    url: Url,
+    http_client: reqwest::Client,
+    logger: Logger,
+    cache: Mutex<Option<CachedForecast>>,
+}
+
+impl MetNorwayClient {
+    fn lock_cache(&self) -> MutexGuard<'_, Option<CachedForecast>> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl WeatherClient for MetNorwayClient {
+    async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+        let last_modified = match &*self.lock_cache() {
+            Some(cached) if cached.is_fresh(SystemTime::now()) => {
+                slog::slog_debug!(self.logger, "MET Norway forecast has not expired yet");
+                return Ok(cached.weather.clone());
+            }
+            Some(cached) => cached.last_modified.clone(),
+            None => None,
+        };
+
+        let mut request = self.http_client.get(self.url.as_str());
+        if let Some(since) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, since);
+        }
+
+        let started = Instant::now();
+        let response = request.send().await?;
+        let status = response.status();
+
+        slog::slog_debug!(self.logger, "MET Norway responded";
+            "status" => status.as_u16(), "latency_ms" => started.elapsed().as_millis() as u64);
+
+        let expires = response
+            .headers()
+            .get(EXPIRES)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok());
+        let last_modified = response
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+
+        if status == StatusCode::NOT_MODIFIED {
+            let mut cache = self.lock_cache();
+            if let Some(cached) = cache.as_mut() {
+                cached.expires = expires;
+                return Ok(cached.weather.clone());
+            }
+            anyhow::bail!("MET Norway responded 304 Not Modified without a cached forecast");
+        }
+
+        if status == StatusCode::NON_AUTHORITATIVE_INFORMATION {
+            slog::slog_warn!(self.logger, "MET Norway API version is deprecated");
+        }
+
+        if !status.is_success() {
+            anyhow::bail!("MET Norway responded with status {}", status);
+        }
+
+        let body = response.text().await?;
+        let weather = parse_current_weather(&body)?;
+
+        *self.lock_cache() = Some(CachedForecast {
+            weather: weather.clone(),
+            last_modified,
+            expires,
+        });
+
+        Ok(weather)
+    }
+}
+
+fn parse_current_weather(body: &str) -> Result<CurrentWeather, anyhow::Error> {
+    serde_json::from_str::<LocationForecast>(body)?.try_into()
+}
+
+#[derive(Debug)]
+pub struct MetNorwayClientBuilder {
+    latitude: f32,
+    longitude: f32,
+    user_agent: String,
+    base_url: Url,
+    logger: Option<Logger>,
+}
+
+impl MetNorwayClientBuilder {
+    /// The user agent must identify the application and preferably its operator
+    ///
+    /// See https://api.met.no/doc/TermsOfService
+    pub fn new(latitude: f32, longitude: f32, user_agent: String) -> Self {
+        let default_base_url = "https://api.met.no/weatherapi/locationforecast/2.0/";
+        let base_url: Url = Url::parse(default_base_url)
+            .unwrap_or_else(|_| panic!("Broken default hardcoded base URL {}", &default_base_url));
+
+        MetNorwayClientBuilder {
+            latitude,
+            longitude,
+            user_agent,
+            base_url,
+            logger: None,
+        }
+    }
+
+    pub fn with_base_url(&mut self, url: Url) {
+        self.base_url = url;
+    }
+
+    pub fn with_logger(&mut self, logger: Logger) {
+        self.logger = Some(logger);
+    }
+
+    pub fn build(self) -> Result<MetNorwayClient, anyhow::Error> {
+        let cb = reqwest::ClientBuilder::new().user_agent(self.user_agent.as_str());
+
+        let client = MetNorwayClient {
+            url: self.get_current_weather_url()?,
+            http_client: cb.build()?,
+            logger: self
+                .logger
+                .unwrap_or_else(|| Logger::root(slog::Discard, slog::o!())),
+            cache: Mutex::new(None),
+        };
+
+        Ok(client)
+    }
+
+    fn get_current_weather_url(&self) -> Result<Url, anyhow::Error> {
+        let mut base = self.base_url.to_string();
+        base.push_str("compact");
+
+        // More than four decimals are rejected as they defeat caching
+        let params = vec![
+            ("lat", format!("{:.4}", self.latitude)),
+            ("lon", format!("{:.4}", self.longitude)),
+        ];
+
+        let url = Url::parse_with_params(&base, params)?;
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::condition::ConditionCategory;
+    use std::time::Duration;
+    use uom::si::{pressure, thermodynamic_temperature};
+
+    static EPSILON: f32 = 0.001;
+
+    #[test]
+    fn first_instant_parsed() {
+        let body = include_str!("../resources/fixtures/met_norway_compact.json");
+        let weather = parse_current_weather(body).unwrap();
+
+        let temperature = weather
+            .get_temperature()
+            .get::<thermodynamic_temperature::degree_celsius>();
+        let pressure = weather.get_pressure().get::<pressure::hectopascal>();
+        let humidity: &f32 = weather.get_humidity().as_ref();
+
+        assert!((17.2 - temperature).abs() < EPSILON, "{}", temperature);
+        assert!((1012.3 - pressure).abs() < EPSILON, "{}", pressure);
+        assert!((71.4 - humidity).abs() < EPSILON, "{}", humidity);
+        assert_eq!(
+            Some(ConditionCategory::Clouds),
+            weather.get_condition().map(|c| c.category)
+        );
+    }
+
+    #[test]
+    fn empty_timeseries_rejected() {
+        let body =
+            r#"{"properties": {"meta": {"updated_at": "2024-06-30T12:31:45Z"}, "timeseries": []}}"#;
+
+        assert!(parse_current_weather(body).is_err());
+    }
+
+    #[test]
+    fn coordinates_truncated() {
+        let url = MetNorwayClientBuilder::new(59.913_868, 10.752_245, "test".to_string())
+            .get_current_weather_url()
+            .unwrap();
+
+        assert_eq!(Some("lat=59.9139&lon=10.7522"), url.query());
+    }
+
+    #[test]
+    fn cache_fresh_until_expiration() {
+        let now = SystemTime::now();
+        let cached = CachedForecast {
+            weather: CurrentWeather::new(283.3, 1001.0, 55.1),
+            last_modified: None,
+            expires: Some(now + Duration::from_secs(60)),
+        };
+
+        assert!(cached.is_fresh(now));
+        assert!(!cached.is_fresh(now + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,60 @@
+// Subset of https://api.met.no/weatherapi/locationforecast/2.0/documentation compact response
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InstantDetails {
+    pub air_pressure_at_sea_level: f32,
+    pub air_temperature: f32,
+    pub relative_humidity: f32,
+    pub cloud_area_fraction: Option<f32>,
+    pub wind_from_direction: Option<f32>,
+    pub wind_speed: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Instant {
+    pub details: InstantDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PeriodDetails {
+    pub precipitation_amount: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PeriodSummary {
+    pub symbol_code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Period {
+    pub summary: Option<PeriodSummary>,
+    pub details: Option<PeriodDetails>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimeStepData {
+    pub instant: Instant,
+    pub next_1_hours: Option<Period>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimeStep {
+    pub time: String,
+    pub data: TimeStepData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Meta {
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Properties {
+    pub meta: Meta,
+    pub timeseries: Vec<TimeStep>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LocationForecast {
+    pub properties: Properties,
+}
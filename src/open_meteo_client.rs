@@ -0,0 +1,172 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use slog::Logger;
+use url::Url;
+
+use crate::domain::current_weather::CurrentWeather;
+use crate::domain::interfaces::WeatherClient;
+use crate::open_meteo_types::{ErrorReport, ForecastCurrent};
+
+const CURRENT_VARIABLES: &str =
+    "temperature_2m,relative_humidity_2m,pressure_msl,surface_pressure,wind_speed_10m,precipitation,weather_code";
+
+pub struct OpenMeteoClient {
+    url: Url,
+    http_client: reqwest::Client,
+    logger: Logger,
+}
+
+#[async_trait]
+impl WeatherClient for OpenMeteoClient {
+    async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
+        let started = Instant::now();
+        let response = self.http_client.get(self.url.as_str()).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        slog::slog_debug!(self.logger, "Open-Meteo responded";
+            "status" => status.as_u16(), "latency_ms" => started.elapsed().as_millis() as u64);
+
+        parse_current_weather(&body)
+    }
+}
+
+fn parse_current_weather(body: &str) -> Result<CurrentWeather, anyhow::Error> {
+    serde_json::from_str::<ForecastCurrent>(body)
+        .map(|v| -> CurrentWeather { v.into() })
+        .map_err(|bad_error| -> String {
+            match serde_json::from_str::<ErrorReport>(body) {
+                Ok(parsed_e) => format!("Open-Meteo error \"{}\"", parsed_e.reason),
+                Err(_) => bad_error.to_string(),
+            }
+        })
+        .map_err(anyhow::Error::msg)
+}
+
+#[derive(Debug)]
+pub struct OpenMeteoClientBuilder {
+    latitude: f32,
+    longitude: f32,
+    base_url: Url,
+    logger: Option<Logger>,
+}
+
+impl OpenMeteoClientBuilder {
+    pub fn new(latitude: f32, longitude: f32) -> Self {
+        let default_base_url = "https://api.open-meteo.com/v1/";
+        let base_url: Url = Url::parse(default_base_url)
+            .unwrap_or_else(|_| panic!("Broken default hardcoded base URL {}", &default_base_url));
+
+        OpenMeteoClientBuilder {
+            latitude,
+            longitude,
+            base_url,
+            logger: None,
+        }
+    }
+
+    pub fn with_base_url(&mut self, url: Url) {
+        self.base_url = url;
+    }
+
+    pub fn with_logger(&mut self, logger: Logger) {
+        self.logger = Some(logger);
+    }
+
+    pub fn build(self) -> Result<OpenMeteoClient, anyhow::Error> {
+        let cb = reqwest::ClientBuilder::new();
+
+        let client = OpenMeteoClient {
+            url: self.get_current_weather_url()?,
+            http_client: cb.build()?,
+            logger: self
+                .logger
+                .unwrap_or_else(|| Logger::root(slog::Discard, slog::o!())),
+        };
+
+        Ok(client)
+    }
+
+    fn get_current_weather_url(&self) -> Result<Url, anyhow::Error> {
+        let mut base = self.base_url.to_string();
+        base.push_str("forecast");
+
+        let params = vec![
+            ("latitude", self.latitude.to_string()),
+            ("longitude", self.longitude.to_string()),
+            ("current", CURRENT_VARIABLES.to_string()),
+            ("timeformat", "unixtime".to_string()),
+            // Only makes the reported UTC offset local, times stay in Unix time
+            ("timezone", "auto".to_string()),
+            ("wind_speed_unit", "ms".to_string()),
+        ];
+
+        let url = Url::parse_with_params(&base, params)?;
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::condition::ConditionCategory;
+    use uom::si::{length, pressure, thermodynamic_temperature, velocity};
+
+    static EPSILON: f32 = 0.001;
+
+    #[test]
+    fn current_weather_parsed() {
+        let body = include_str!("../resources/fixtures/open_meteo_current.json");
+        let weather = parse_current_weather(body).unwrap();
+
+        let temperature = weather
+            .get_temperature()
+            .get::<thermodynamic_temperature::degree_celsius>();
+        let pressure = weather.get_pressure().get::<pressure::hectopascal>();
+        let humidity: &f32 = weather.get_humidity().as_ref();
+
+        assert!((23.4 - temperature).abs() < EPSILON, "{}", temperature);
+        assert!((1014.6 - pressure).abs() < EPSILON, "{}", pressure);
+        assert!((58.0 - humidity).abs() < EPSILON, "{}", humidity);
+
+        let wind = weather
+            .get_wind_speed()
+            .unwrap()
+            .get::<velocity::meter_per_second>();
+        assert!((3.2 - wind).abs() < EPSILON, "{}", wind);
+
+        let precipitation = weather
+            .get_precipitation()
+            .unwrap()
+            .get::<length::millimeter>();
+        assert!((0.4 - precipitation).abs() < EPSILON, "{}", precipitation);
+        assert_eq!(Some(7200), weather.get_utc_offset());
+
+        let condition = weather.get_condition().unwrap();
+        assert_eq!(Some(61), condition.id);
+        assert_eq!(ConditionCategory::Rain, condition.category);
+    }
+
+    #[test]
+    fn error_reason_reported() {
+        let body = include_str!("../resources/fixtures/open_meteo_error.json");
+        let error = parse_current_weather(body).unwrap_err();
+
+        assert!(error.to_string().contains("Latitude must be in range"));
+    }
+
+    #[test]
+    fn url_contains_coordinates() {
+        let url = OpenMeteoClientBuilder::new(50.08, 14.42)
+            .get_current_weather_url()
+            .unwrap();
+
+        assert_eq!(Some("api.open-meteo.com"), url.host_str());
+        assert_eq!("/v1/forecast", url.path());
+        assert!(url
+            .query()
+            .unwrap()
+            .contains("latitude=50.08&longitude=14.42"));
+    }
+}
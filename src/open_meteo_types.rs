@@ -0,0 +1,31 @@
+// Subset of https://open-meteo.com/en/docs forecast API response
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Current {
+    pub time: u64,
+    pub interval: u32,
+    pub temperature_2m: f32,
+    pub relative_humidity_2m: f32,
+    pub pressure_msl: f32,
+    pub surface_pressure: Option<f32>,
+    pub wind_speed_10m: Option<f32>,
+    pub precipitation: Option<f32>,
+    /// WMO weather interpretation code
+    pub weather_code: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ForecastCurrent {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub utc_offset_seconds: i32,
+    pub timezone: String,
+    pub elevation: Option<f32>,
+    pub current: Current,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorReport {
+    pub error: bool,
+    pub reason: String,
+}
@@ -1,30 +1,157 @@
+use std::time::{Instant, SystemTime};
+
 use url::Url;
 
 use async_trait::async_trait;
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use slog::Logger;
 
+use crate::app::quota::QuotaTracker;
 use crate::domain::current_weather::CurrentWeather;
 use crate::domain::interfaces::WeatherClient;
+use crate::http_cache::{CacheOutcome, HttpCache, Lookup};
 use crate::location_specifier::LocationSpecifier;
 use crate::weather_types::{ErrorReport, WeatherReportCurrent};
 
+const MILE_PER_HOUR: f32 = 0.447_04;
+
+/// Units OpenWeatherMap reports the values in, requested by the `units` parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnitSystem {
+    /// Kelvins and metres per second
+    Standard,
+    /// Degrees Celsius and metres per second
+    Metric,
+    /// Degrees Fahrenheit and miles per hour
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn param(self) -> &'static str {
+        match self {
+            UnitSystem::Standard => "standard",
+            UnitSystem::Metric => "metric",
+            UnitSystem::Imperial => "imperial",
+        }
+    }
+
+    fn to_kelvin(self, value: f32) -> f32 {
+        match self {
+            UnitSystem::Standard => value,
+            UnitSystem::Metric => value + 273.15,
+            UnitSystem::Imperial => (value - 32.0) * 5.0 / 9.0 + 273.15,
+        }
+    }
+
+    fn to_meter_per_second(self, value: f32) -> f32 {
+        match self {
+            UnitSystem::Imperial => value * MILE_PER_HOUR,
+            _ => value,
+        }
+    }
+
+    /// Converts the report to the standard units the domain conversion expects
+    fn normalize(self, mut report: WeatherReportCurrent) -> WeatherReportCurrent {
+        report.main.temp = self.to_kelvin(report.main.temp);
+        report.main.temp_min = self.to_kelvin(report.main.temp_min);
+        report.main.temp_max = self.to_kelvin(report.main.temp_max);
+        report.wind.speed = self.to_meter_per_second(report.wind.speed);
+        report.wind.gust = report.wind.gust.map(|g| self.to_meter_per_second(g));
+
+        report
+    }
+}
+
 pub struct OpenWeatherMapClient {
     url: Url,
+    units: UnitSystem,
     http_client: reqwest::Client,
+    quota: Option<QuotaTracker>,
+    cache: Option<HttpCache>,
+    logger: Logger,
+}
+
+impl OpenWeatherMapClient {
+    /// Response body, a fresh cached one saves the request
+    async fn fetch_body(&self) -> Result<String, anyhow::Error> {
+        let now = SystemTime::now();
+        let cached = match &self.cache {
+            Some(cache) => cache.lookup(&self.url, now),
+            None => Lookup::Miss,
+        };
+        let stale = match cached {
+            Lookup::Fresh(entry) => {
+                self.record_cache(CacheOutcome::Hit);
+                return Ok(entry.body);
+            }
+            Lookup::Stale(entry) => Some(entry),
+            Lookup::Miss => None,
+        };
+
+        let mut request = self.http_client.get(self.url.as_str());
+        if let Some(entry) = &stale {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag.as_str());
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+            }
+        }
+
+        let started = Instant::now();
+        if let Some(quota) = &self.quota {
+            quota.record_call(now);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        slog::slog_debug!(self.logger, "OpenWeatherMap responded";
+            "status" => status.as_u16(), "latency_ms" => started.elapsed().as_millis() as u64);
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return Ok(response.text().await?),
+        };
+        if let (StatusCode::NOT_MODIFIED, Some(mut entry)) = (status, stale) {
+            if let Err(e) = cache.revalidated(&self.url, &mut entry, &headers, now) {
+                slog::slog_warn!(self.logger, "HTTP cache not updated"; "error" => format!("{:#}", e));
+            }
+            self.record_cache(CacheOutcome::Revalidated);
+            return Ok(entry.body);
+        }
+
+        let body = response.text().await?;
+        if status.is_success() {
+            if let Err(e) = cache.store(&self.url, body.clone(), &headers, now) {
+                slog::slog_warn!(self.logger, "HTTP cache not updated"; "error" => format!("{:#}", e));
+            }
+        }
+        self.record_cache(CacheOutcome::Miss);
+
+        Ok(body)
+    }
+
+    fn record_cache(&self, outcome: CacheOutcome) {
+        if let Some(cache) = &self.cache {
+            cache.record(outcome);
+        }
+        match outcome {
+            CacheOutcome::Miss => {}
+            _ => slog::slog_info!(self.logger, "OpenWeatherMap response served from cache";
+                "cache" => outcome.name()),
+        }
+    }
 }
 
 #[async_trait]
 impl WeatherClient for OpenWeatherMapClient {
     async fn get_current_weather(&self) -> Result<CurrentWeather, anyhow::Error> {
-        let body = self
-            .http_client
-            .get(self.url.as_str())
-            .send()
-            .await?
-            .text()
-            .await?;
+        let body = self.fetch_body().await?;
 
         serde_json::from_str::<WeatherReportCurrent>(body.as_ref())
-            .map(|v| -> CurrentWeather { v.into() })
+            .map(|v| -> CurrentWeather { self.units.normalize(v).into() })
             .map_err(|bad_error| -> String {
                 let parsed_error = serde_json::from_str::<ErrorReport>(body.as_ref());
                 match parsed_error {
@@ -47,6 +174,11 @@ where
     location_specifier: LocationSpecifier<'a>,
     api_key: T,
     base_url: Url,
+    language: Option<String>,
+    units: UnitSystem,
+    quota: Option<QuotaTracker>,
+    cache: Option<HttpCache>,
+    logger: Option<Logger>,
 }
 
 impl<'a, T> OpenWeatherMapClientBuilder<'a, T>
@@ -62,6 +194,11 @@ where
             location_specifier,
             api_key,
             base_url,
+            language: None,
+            units: UnitSystem::Standard,
+            quota: None,
+            cache: None,
+            logger: None,
         }
     }
 
@@ -70,6 +207,30 @@ where
         self.base_url = url;
     }
 
+    /// Language of condition descriptions, e.g. "cs"
+    pub fn with_language(&mut self, language: String) {
+        self.language = Some(language);
+    }
+
+    /// Units the API reports in, the values are converted back for the publisher
+    pub fn with_units(&mut self, units: UnitSystem) {
+        self.units = units;
+    }
+
+    /// Counts the calls against the budget of the key
+    pub fn with_quota(&mut self, quota: QuotaTracker) {
+        self.quota = Some(quota);
+    }
+
+    /// Keeps responses on disk, also across restarts
+    pub fn with_cache(&mut self, cache: HttpCache) {
+        self.cache = Some(cache);
+    }
+
+    pub fn with_logger(&mut self, logger: Logger) {
+        self.logger = Some(logger);
+    }
+
     pub fn build(self) -> Result<OpenWeatherMapClient, anyhow::Error> {
         let cb = reqwest::ClientBuilder::new();
 
@@ -78,8 +239,16 @@ where
                 &self.location_specifier,
                 self.api_key,
                 self.base_url,
+                self.language.as_deref(),
+                self.units,
             )?,
+            units: self.units,
             http_client: cb.build()?,
+            quota: self.quota,
+            cache: self.cache,
+            logger: self
+                .logger
+                .unwrap_or_else(|| Logger::root(slog::Discard, slog::o!())),
         };
 
         Ok(client)
@@ -89,14 +258,83 @@ where
         location: &LocationSpecifier,
         key: T,
         base_url: Url,
+        language: Option<&str>,
+        units: UnitSystem,
     ) -> Result<Url, anyhow::Error> {
         let mut base = base_url.into_string();
         let mut params = location.format();
 
         base.push_str("weather");
         params.push(("APPID".to_string(), key.into()));
+        params.push(("units".to_string(), units.param().to_string()));
+        if let Some(language) = language {
+            params.push(("lang".to_string(), language.to_string()));
+        }
 
         let url = Url::parse_with_params(&base, params)?;
         Ok(url)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const METRIC: &str = include_str!("../resources/fixtures/owm_current.json");
+    const IMPERIAL: &str = include_str!("../resources/fixtures/owm_current_imperial.json");
+
+    fn report(body: &str, units: UnitSystem) -> WeatherReportCurrent {
+        units.normalize(serde_json::from_str(body).unwrap())
+    }
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!(
+            (expected - actual).abs() < 0.01,
+            "{} != {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn reports_normalized_to_standard_units() {
+        let metric = report(METRIC, UnitSystem::Metric);
+        assert_close(283.3, metric.main.temp);
+        assert_close(282.15, metric.main.temp_min);
+        assert_close(4.1, metric.wind.speed);
+
+        // The same observation in °F and mph
+        let imperial = report(IMPERIAL, UnitSystem::Imperial);
+        assert_close(283.3, imperial.main.temp);
+        assert_close(282.15, imperial.main.temp_min);
+        assert_close(4.1, imperial.wind.speed);
+        assert_close(7.2, imperial.wind.gust.unwrap());
+
+        let standard = report(METRIC, UnitSystem::Standard);
+        assert_close(10.15, standard.main.temp);
+    }
+
+    #[test]
+    fn location_name_kept() {
+        let weather: CurrentWeather = report(METRIC, UnitSystem::Metric).into();
+
+        assert_eq!(Some("Prague"), weather.get_location_name());
+    }
+
+    #[test]
+    fn units_requested() {
+        let url = OpenWeatherMapClientBuilder::<String>::get_current_weather_url(
+            &LocationSpecifier::CityId("3067696"),
+            "key".to_string(),
+            Url::parse("https://api.openweathermap.org/data/2.5/").unwrap(),
+            None,
+            UnitSystem::Metric,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "https://api.openweathermap.org/data/2.5/weather?id=3067696&APPID=key&units=metric",
+            url.as_str()
+        );
+    }
+}
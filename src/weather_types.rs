@@ -40,12 +40,15 @@ pub struct Wind {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Rain {
+pub struct Volume {
+    #[serde(rename = "1h")]
+    pub one_h: Option<f32>,
     #[serde(rename = "3h")]
     pub three_h: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[allow(dead_code)]
 pub struct System {
     pub pod: String,
 }
@@ -78,8 +81,12 @@ pub struct WeatherReportCurrent {
     pub visibility: u32,
     pub wind: Wind,
     pub clouds: Clouds,
+    pub rain: Option<Volume>,
+    pub snow: Option<Volume>,
     pub dt: u64,
     pub sys: Sys,
+    /// Shift from UTC in seconds
+    pub timezone: Option<i32>,
     pub id: u64,
     pub name: String,
     #[serde(skip_deserializing)]